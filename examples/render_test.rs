@@ -58,6 +58,7 @@ pub struct InstanceData {
 vulkano::impl_vertex!(InstanceData, src, color, transform);
 
 struct MainState {
+    render_pass: usize,
     test_shader: ShaderId,
     camera: Arc<OrthographicCamera>,
     image: Arc<Image>,
@@ -91,15 +92,15 @@ impl event::EventHandler for MainState {
             .vertex::<TestVertex>()
             .instance::<InstanceData>();
 
-        let test_shader = Arc::new(ledge::graphics::shader::Shader {
-            vertex: vs.entry_point("main").unwrap(),
-            fragment: fs.entry_point("main").unwrap(),
-            topology: graphics::shader::VertexTopology::TriangleFan,
-            // vertex_definition: v_type,
-        });
+        let test_shader = Arc::new(ledge::graphics::shader::Shader::new(
+            vs,
+            fs,
+            graphics::shader::VertexTopology::TriangleFan,
+        ));
 
         let mut render_pass = crate::graphics::render_pass::RenderPass::new( 
             renderer.queue.clone(),
+            renderer.resources.clone(),
             vulkano::ordered_passes_renderpass!(renderer.device.clone(),
                 attachments: {
                     final_color: {
@@ -122,16 +123,18 @@ impl event::EventHandler for MainState {
         let test_shader = render_pass.register_shader(test_shader, v_type).unwrap();
 
         renderer.render_passes.push(render_pass);
+        let render_pass = renderer.render_passes.len() - 1;
 
         let image = Arc::new(
             Image::new(
                 renderer.queue.clone(), 
                 renderer.samplers[0].clone(), 
                 "examples/images/pokeball.png",
-            ),
+            ).unwrap(),
         );
         
         MainState{
+            render_pass,
             test_shader,
             camera: Arc::new(OrthographicCamera::new(1.0, 1000.0)),
             image: image,
@@ -151,7 +154,7 @@ impl event::EventHandler for MainState {
         let clear = Color::grey();
 
         let final_image = renderer.final_image();
-        let mut frame = renderer.render_passes[0].frame(
+        let mut frame = renderer.render_passes[self.render_pass].frame(
             clear.into(), 
             before_future, 
             final_image, 
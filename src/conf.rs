@@ -10,6 +10,18 @@ pub struct WindowMode {
     pub(crate) min_height: f32,
     max_height: f32,
     pub(crate) resizable: bool,
+    pub(crate) ui_units: UiUnits,
+    pub(crate) pause_when_minimized: bool,
+}
+
+/// The units `Interface::ui_camera` draws in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UiUnits {
+    /// Physical pixels, the UI gets smaller on high density displays.
+    Pixels,
+    /// Logical points, physical pixels divided by the window's scale factor, so the UI keeps
+    /// the same apparent size on every display.
+    Points,
 }
 
 impl WindowMode {
@@ -25,14 +37,28 @@ impl WindowMode {
             max_width: 0.0,
             max_height: 0.0,
             resizable: true,
+            ui_units: UiUnits::Pixels,
+            pause_when_minimized: false,
         }
     }
+
+    pub fn ui_units(mut self, units: UiUnits) -> Self {
+        self.ui_units = units;
+        self
+    }
+
+    /// Stops calling `EventHandler::update` while the window is minimized. Drawing always
+    /// stops while minimized.
+    pub fn pause_when_minimized(mut self, pause: bool) -> Self {
+        self.pause_when_minimized = pause;
+        self
+    }
 }
 
 #[allow(unused)]
 pub struct WindowSetup {
     pub(crate) title: String,
-    // samples:
+    pub(crate) samples: u32,
     vsync: bool,
     icon: String,
     srgb: bool,
@@ -42,11 +68,21 @@ impl WindowSetup {
     pub fn default() -> Self {
         Self {
             title: "Empty Title".to_string(),
+            samples: 1,
             vsync: false,
             icon: "".to_string(),
             srgb: true,
         }
     }
+
+    /// Multisample anti-aliasing samples per pixel for the default render pass, 1 disables it.
+    ///
+    /// Lowered to the highest count the device supports when it is not supported,
+    /// `Renderer::samples` is the count in use.
+    pub fn samples(mut self, samples: u32) -> Self {
+        self.samples = samples;
+        self
+    }
 }
 
 #[allow(unused)]
@@ -56,9 +92,115 @@ enum FullscreenType {
     WFullScreen,
 }
 
+/// How the fixed size image of a `VirtualResolution` is scaled to fill the window.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScalingMode {
+    /// Fills the whole window, distorting the image when the aspect ratios differ.
+    Stretch,
+    /// Scales as large as fits while keeping the aspect ratio, with black bars on the sides
+    /// that are left over.
+    Fit,
+    /// Like `Fit`, but only by whole multiples so every logical pixel covers the same number
+    /// of window pixels. Falls back to `Fit` when the window is smaller than the resolution.
+    Integer,
+}
+
+/// A fixed logical resolution the game renders at regardless of the window size.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VirtualResolution {
+    pub width: u32,
+    pub height: u32,
+    pub scaling: ScalingMode,
+}
+
+impl VirtualResolution {
+    pub fn new(width: u32, height: u32, scaling: ScalingMode) -> Self {
+        Self {
+            width,
+            height,
+            scaling,
+        }
+    }
+
+    /// The rect of a `window` sized surface the image is presented in, as x, y, width and
+    /// height in pixels. `None` when either size is zero or the image scales to nothing.
+    pub fn present_rect(&self, window: [u32; 2]) -> Option<[u32; 4]> {
+        if self.width == 0 || self.height == 0 || window[0] == 0 || window[1] == 0 {
+            return None;
+        }
+
+        let (width, height) = (self.width as f32, self.height as f32);
+        let scale_x = window[0] as f32 / width;
+        let scale_y = window[1] as f32 / height;
+
+        let scale = match self.scaling {
+            ScalingMode::Stretch => return Some([0, 0, window[0], window[1]]),
+            ScalingMode::Fit => scale_x.min(scale_y),
+            ScalingMode::Integer if scale_x.min(scale_y) >= 1.0 => scale_x.min(scale_y).floor(),
+            ScalingMode::Integer => scale_x.min(scale_y),
+        };
+
+        let w = ((width * scale).round() as u32).min(window[0]);
+        let h = ((height * scale).round() as u32).min(window[1]);
+
+        if w == 0 || h == 0 {
+            return None;
+        }
+
+        Some([(window[0] - w) / 2, (window[1] - h) / 2, w, h])
+    }
+
+    /// Maps a position in pixels of a `window` sized surface to logical pixels. Positions on
+    /// the bars around the image map outside of `0..width` and `0..height`.
+    pub fn to_logical(&self, window: [u32; 2], position: (f64, f64)) -> (f64, f64) {
+        let [x, y, w, h] = match self.present_rect(window) {
+            Some(rect) => rect,
+            None => return position,
+        };
+
+        (
+            (position.0 - x as f64) * self.width as f64 / w as f64,
+            (position.1 - y as f64) * self.height as f64 / h as f64,
+        )
+    }
+}
+
+/// Which device the renderer uses when the system has more than one.
+///
+/// The `LEDGE_DEVICE` environment variable overrides it, see `graphics::adapter`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DevicePreference {
+    /// Discrete over integrated over virtual GPUs, then CPU implementations.
+    HighPerformance,
+    /// Integrated GPUs first, to save battery.
+    LowPower,
+    /// The first device whose name contains this, ignoring case.
+    Name(String),
+    /// The device at this index in `graphics::adapter::enumerate`.
+    Index(usize),
+}
+
+/// Whether the Vulkan validation layer runs and which of its messages are logged.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DebugLevel {
+    /// No validation.
+    Off,
+    Errors,
+    /// Errors and warnings, including performance warnings.
+    Warnings,
+    /// Errors, warnings and informational messages.
+    Info,
+    /// Everything the layers and loader report.
+    Verbose,
+}
+
 pub struct Conf {
     pub(crate) window_mode: WindowMode,
     pub(crate) window_setup: WindowSetup,
+    pub(crate) virtual_resolution: Option<VirtualResolution>,
+    pub(crate) device: DevicePreference,
+    pub(crate) debug: DebugLevel,
+    pub(crate) frames_in_flight: usize,
 }
 
 impl Conf {
@@ -72,6 +214,94 @@ impl Conf {
         Self {
             window_mode: WindowMode::default(),
             window_setup: WindowSetup::default(),
+            virtual_resolution: None,
+            device: DevicePreference::HighPerformance,
+            debug: DebugLevel::Off,
+            frames_in_flight: crate::graphics::resources::FRAMES_IN_FLIGHT,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stretch_fills_the_window() {
+        let resolution = VirtualResolution::new(320, 180, ScalingMode::Stretch);
+
+        assert_eq!(resolution.present_rect([1000, 1000]), Some([0, 0, 1000, 1000]));
+    }
+
+    #[test]
+    fn fit_keeps_the_aspect_ratio() {
+        let resolution = VirtualResolution::new(320, 180, ScalingMode::Fit);
+
+        // Bars above and below.
+        assert_eq!(resolution.present_rect([1280, 1000]), Some([0, 140, 1280, 720]));
+        // Bars on the sides.
+        assert_eq!(resolution.present_rect([1000, 360]), Some([180, 0, 640, 360]));
+        assert_eq!(resolution.present_rect([1920, 1080]), Some([0, 0, 1920, 1080]));
+    }
+
+    #[test]
+    fn integer_scales_by_whole_multiples() {
+        let resolution = VirtualResolution::new(320, 180, ScalingMode::Integer);
+
+        assert_eq!(resolution.present_rect([1000, 700]), Some([20, 80, 960, 540]));
+        assert_eq!(resolution.present_rect([640, 360]), Some([0, 0, 640, 360]));
+    }
+
+    #[test]
+    fn integer_falls_back_to_fit_in_small_windows() {
+        let resolution = VirtualResolution::new(320, 180, ScalingMode::Integer);
+
+        assert_eq!(resolution.present_rect([160, 120]), Some([0, 15, 160, 90]));
+    }
+
+    #[test]
+    fn empty_window_has_no_rect() {
+        for scaling in [ScalingMode::Stretch, ScalingMode::Fit, ScalingMode::Integer] {
+            let resolution = VirtualResolution::new(320, 180, scaling);
+
+            assert_eq!(resolution.present_rect([0, 0]), None);
+            assert_eq!(resolution.present_rect([1280, 0]), None);
+        }
+    }
+
+    #[test]
+    fn empty_resolution_has_no_rect() {
+        for scaling in [ScalingMode::Stretch, ScalingMode::Fit, ScalingMode::Integer] {
+            let resolution = VirtualResolution { width: 0, height: 180, scaling };
+
+            assert_eq!(resolution.present_rect([1280, 720]), None);
+            assert_eq!(resolution.to_logical([1280, 720], (12.0, 34.0)), (12.0, 34.0));
         }
     }
+
+    #[test]
+    fn to_logical_maps_the_presented_image() {
+        let resolution = VirtualResolution::new(320, 180, ScalingMode::Integer);
+        let window = [1000, 700];
+
+        assert_eq!(resolution.to_logical(window, (20.0, 80.0)), (0.0, 0.0));
+        assert_eq!(resolution.to_logical(window, (980.0, 620.0)), (320.0, 180.0));
+        assert_eq!(resolution.to_logical(window, (500.0, 350.0)), (160.0, 90.0));
+    }
+
+    #[test]
+    fn to_logical_maps_bars_outside_the_image() {
+        let resolution = VirtualResolution::new(320, 180, ScalingMode::Fit);
+        let (x, y) = resolution.to_logical([1280, 1000], (0.0, 0.0));
+
+        assert_eq!(x, 0.0);
+        assert!(y < 0.0);
+    }
+
+    #[test]
+    fn to_logical_passes_through_without_an_image() {
+        let resolution = VirtualResolution::new(320, 180, ScalingMode::Fit);
+
+        assert_eq!(resolution.to_logical([0, 0], (12.0, 34.0)), (12.0, 34.0));
+    }
 }
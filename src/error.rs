@@ -1,6 +1,33 @@
+use std::fmt;
+
 #[derive(Debug)]
 pub enum GameError {
-    // TODO Implement.
+    /// A shader failed to compile. `line` and `column` start at 1 and are 0 when the
+    /// compiler could not point at a location in the source.
+    ShaderCompileError {
+        file: String,
+        line: u32,
+        column: u32,
+        message: String,
+    },
+    /// The descriptors or push constants given for a draw do not match the shader's layout.
+    BindingError(String),
+}
+
+impl fmt::Display for GameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GameError::ShaderCompileError {
+                file,
+                line,
+                column,
+                message,
+            } => write!(f, "{}:{}:{}: {}", file, line, column, message),
+            GameError::BindingError(message) => write!(f, "Shader binding mismatch: {}", message),
+        }
+    }
 }
 
+impl std::error::Error for GameError {}
+
 pub type GameResult<T = ()> = Result<T, GameError>;
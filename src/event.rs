@@ -1,5 +1,5 @@
 // use std::time::{Duration, SystemTime};
-use crate::{interface::*};
+use crate::{graphics::Color, interface::*};
 use std::time;
 use std::thread;
 use vulkano::sync::GpuFuture;
@@ -30,6 +30,10 @@ where
                     interface.renderer.recreate_swapchain = true;
                     game_state.resize(size.width, size.height).unwrap();
                 }
+                WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                    interface.renderer.recreate_swapchain = true;
+                    game_state.resize(new_inner_size.width, new_inner_size.height).unwrap();
+                }
                 _ => {}
             },
             Event::DeviceEvent { .. } => {}
@@ -43,20 +47,30 @@ where
 
                 // 
 
+                let minimized = interface.renderer.is_minimized();
+
                 let upda = time::Instant::now();
-                if let Err(e) = game_state.update(interface) {
-                    println!("Error on EventHandler::update(): {:?}", e);
+                if !(minimized && interface.pause_when_minimized) {
+                    if let Err(e) = game_state.update(interface) {
+                        println!("Error on EventHandler::update(): {:?}", e);
+                    }
                 }
 
                 let update_time = 1000. * upda.elapsed().as_secs_f32();
 
                 let draw = time::Instant::now();
 
-                let mut future = interface.renderer.begin_frame().unwrap();
-
-                future = game_state.draw(interface, future).unwrap();
-
-                interface.renderer.end_frame(future);
+                match interface.renderer.begin_frame() {
+                    Ok(Some(frame)) => {
+                        if let Err(e) = draw_frame(&mut game_state, interface, frame.future()) {
+                            println!("Error on EventHandler::draw(): {:?}", e);
+                            interface.renderer.cancel_frame(frame);
+                        }
+                    }
+                    // Minimized or waiting on the swapchain, nothing to draw this time.
+                    Ok(None) => interface.debug_draw.clear(),
+                    Err(e) => println!("Failed to begin frame: {:?}", e),
+                }
 
                 let draw_time = 1000. * draw.elapsed().as_secs_f32();
 
@@ -81,6 +95,29 @@ where
     });
 }
 
+fn draw_frame<S: EventHandler>(
+    game_state: &mut S,
+    interface: &mut Interface,
+    future: Box<dyn GpuFuture>,
+) -> Result<()> {
+    let mut future = game_state.draw(interface, future)?;
+
+    if interface.debug_draw.stats_overlay {
+        let stats = interface.renderer.stats;
+        interface.debug_draw.stats([-0.98, -0.98], &stats, Color::white());
+    }
+
+    let final_image = interface.renderer.final_image();
+    future = interface.debug_draw.draw(future, final_image)?;
+
+    interface.renderer.end_frame(future);
+    interface
+        .timer_state
+        .set_gpu_timings(interface.renderer.resources.gpu_timings());
+
+    Ok(())
+}
+
 pub trait EventHandler {
     fn start(interface: &mut Interface) -> Self;
     fn update(&mut self, interface: &mut Interface) -> Result<()>;
@@ -0,0 +1,139 @@
+use crate::conf::DevicePreference;
+
+use std::sync::Arc;
+use vulkano::device::physical::{PhysicalDevice, PhysicalDeviceType, QueueFamily};
+use vulkano::device::{DeviceExtensions, Features};
+use vulkano::instance::{Instance, InstanceCreateInfo};
+use vulkano::swapchain::Surface;
+use vulkano::Version;
+use winit::window::Window;
+
+use anyhow::{anyhow, Result};
+
+/// Environment variable that overrides the `DevicePreference` from `Conf`. A number selects the
+/// device at that index, anything else the first device whose name contains it.
+pub const DEVICE_ENV_VAR: &str = "LEDGE_DEVICE";
+
+/// A description of a Vulkan device for choosing between them.
+#[derive(Clone, Debug)]
+pub struct AdapterInfo {
+    /// Position in the system's device list, what `DevicePreference::Index` selects.
+    pub index: usize,
+    pub name: String,
+    pub device_type: PhysicalDeviceType,
+    pub vendor_id: u32,
+    pub driver_version: u32,
+    pub api_version: Version,
+    pub max_image_dimension: u32,
+    pub max_push_constants_size: u32,
+    pub max_bound_descriptor_sets: u32,
+    pub max_compute_work_group_count: [u32; 3],
+    pub max_sampler_anisotropy: f32,
+    pub features: Features,
+}
+
+impl AdapterInfo {
+    pub fn new(physical: PhysicalDevice) -> Self {
+        let properties = physical.properties();
+
+        Self {
+            index: physical.index(),
+            name: properties.device_name.clone(),
+            device_type: properties.device_type,
+            vendor_id: properties.vendor_id,
+            driver_version: properties.driver_version,
+            api_version: physical.api_version(),
+            max_image_dimension: properties.max_image_dimension2_d,
+            max_push_constants_size: properties.max_push_constants_size,
+            max_bound_descriptor_sets: properties.max_bound_descriptor_sets,
+            max_compute_work_group_count: properties.max_compute_work_group_count,
+            max_sampler_anisotropy: properties.max_sampler_anisotropy,
+            features: physical.supported_features().clone(),
+        }
+    }
+}
+
+/// Lists every Vulkan device on the system, for example to fill a settings menu before the
+/// renderer is created.
+pub fn enumerate() -> Result<Vec<AdapterInfo>> {
+    let instance = Instance::new(InstanceCreateInfo {
+        application_version: Version::V1_1,
+        ..Default::default()
+    })?;
+
+    Ok(PhysicalDevice::enumerate(&instance).map(AdapterInfo::new).collect())
+}
+
+/// Picks a device that can present to `surface` following `preference`, or the environment
+/// override when it is set.
+///
+/// When no device matches a name or index the highest performance device is used instead.
+pub(crate) fn select<'a>(
+    instance: &'a Arc<Instance>,
+    surface: &Arc<Surface<Window>>,
+    extensions: &DeviceExtensions,
+    preference: &DevicePreference,
+) -> Result<(PhysicalDevice<'a>, QueueFamily<'a>)> {
+    let candidates: Vec<_> = PhysicalDevice::enumerate(instance)
+        .filter(|&p| p.supported_extensions().is_superset_of(extensions))
+        .filter_map(|p| {
+            p.queue_families()
+                .find(|&q| q.supports_graphics() && q.supports_surface(surface).unwrap_or(false))
+                .map(|q| (p, q))
+        })
+        .collect();
+
+    let preference = match std::env::var(DEVICE_ENV_VAR) {
+        Ok(value) => match value.parse() {
+            Ok(index) => DevicePreference::Index(index),
+            Err(_) => DevicePreference::Name(value),
+        },
+        Err(_) => preference.clone(),
+    };
+
+    let by_rank = |preference: &DevicePreference| {
+        candidates
+            .iter()
+            .min_by_key(|(p, _)| rank(preference, p.properties().device_type))
+            .copied()
+    };
+
+    let chosen = match &preference {
+        DevicePreference::Index(index) => candidates.iter().find(|(p, _)| p.index() == *index).copied(),
+        DevicePreference::Name(name) => {
+            let name = name.to_lowercase();
+            candidates
+                .iter()
+                .find(|(p, _)| p.properties().device_name.to_lowercase().contains(&name))
+                .copied()
+        }
+        _ => by_rank(&preference),
+    };
+
+    match chosen {
+        Some(chosen) => Ok(chosen),
+        None => {
+            let fallback = by_rank(&DevicePreference::HighPerformance)
+                .ok_or_else(|| anyhow!("No device can draw to the window"))?;
+            println!(
+                "No device matches {:?}, using {}",
+                preference,
+                fallback.0.properties().device_name
+            );
+            Ok(fallback)
+        }
+    }
+}
+
+// Lower is better.
+fn rank(preference: &DevicePreference, device_type: PhysicalDeviceType) -> u32 {
+    match (device_type, preference) {
+        (PhysicalDeviceType::IntegratedGpu, DevicePreference::LowPower) => 0,
+        (PhysicalDeviceType::DiscreteGpu, DevicePreference::LowPower) => 1,
+        (PhysicalDeviceType::DiscreteGpu, _) => 0,
+        (PhysicalDeviceType::IntegratedGpu, _) => 1,
+        (PhysicalDeviceType::VirtualGpu, _) => 2,
+        (PhysicalDeviceType::Cpu, _) => 3,
+        (PhysicalDeviceType::Other, _) => 4,
+    }
+}
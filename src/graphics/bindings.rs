@@ -0,0 +1,278 @@
+use crate::error::{GameError, GameResult};
+
+use bytemuck::Pod;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use vulkano::buffer::{BufferAccess, BufferUsage, CpuAccessibleBuffer};
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::descriptor_set::layout::DescriptorType;
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::Device;
+use vulkano::image::view::ImageViewAbstract;
+use vulkano::pipeline::layout::PipelineLayout;
+use vulkano::pipeline::PipelineBindPoint;
+use vulkano::sampler::Sampler;
+
+use anyhow::Result;
+
+/// Descriptors and push constants bound by set and binding, shared by graphics draws through
+/// `PipelineData` and compute dispatches.
+///
+/// Before recording, the bindings are checked against the layout reflected from the shader, so
+/// a binding the shader does not declare, a descriptor of the wrong type or a missing binding in
+/// a set that is written fails with a `GameError::BindingError`.
+pub struct Bindings {
+    device: Arc<Device>,
+    bindings: Vec<Binding>,
+    push_constants: Option<Vec<u8>>,
+    uploaded: usize,
+    // The first error from creating a buffer, returned by `record`.
+    error: Option<anyhow::Error>,
+}
+
+// The most push constant bytes `record` can push, twice the 128 bytes every device supports.
+const MAX_PUSH_CONSTANTS: usize = 256;
+
+struct Binding {
+    set: u32,
+    binding: u32,
+    kind: BindingKind,
+    write: WriteDescriptorSet,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum BindingKind {
+    UniformBuffer,
+    StorageBuffer,
+    ImageSampler,
+    SampledImage,
+    Sampler,
+    StorageImage,
+}
+
+impl BindingKind {
+    fn matches(self, ty: DescriptorType) -> bool {
+        match self {
+            BindingKind::UniformBuffer => matches!(ty, DescriptorType::UniformBuffer | DescriptorType::UniformBufferDynamic),
+            BindingKind::StorageBuffer => matches!(ty, DescriptorType::StorageBuffer | DescriptorType::StorageBufferDynamic),
+            BindingKind::ImageSampler => ty == DescriptorType::CombinedImageSampler,
+            BindingKind::SampledImage => ty == DescriptorType::SampledImage,
+            BindingKind::Sampler => ty == DescriptorType::Sampler,
+            BindingKind::StorageImage => ty == DescriptorType::StorageImage,
+        }
+    }
+}
+
+impl Bindings {
+    pub fn new(device: Arc<Device>) -> Self {
+        Self {
+            device,
+            bindings: Vec::new(),
+            push_constants: None,
+            uploaded: 0,
+            error: None,
+        }
+    }
+
+    fn bind(mut self, set: u32, binding: u32, kind: BindingKind, write: WriteDescriptorSet) -> Self {
+        self.bindings.retain(|b| b.set != set || b.binding != binding);
+        self.bindings.push(Binding { set, binding, kind, write });
+        self.bindings.sort_by_key(|b| (b.set, b.binding));
+
+        self
+    }
+
+    pub fn uniform_buffer(self, set: u32, binding: u32, buffer: Arc<dyn BufferAccess>) -> Self {
+        self.bind(set, binding, BindingKind::UniformBuffer, WriteDescriptorSet::buffer(binding, buffer))
+    }
+
+    /// Uploads `data` into a new uniform buffer and binds it. Failing to create the buffer is
+    /// reported by `record`.
+    pub fn uniform<T: Pod + Send + Sync>(mut self, set: u32, binding: u32, data: T) -> Self {
+        self.uploaded += std::mem::size_of::<T>();
+        let buffer = CpuAccessibleBuffer::from_data(
+            self.device.clone(),
+            BufferUsage::uniform_buffer(),
+            false,
+            data,
+        );
+
+        match buffer {
+            Ok(buffer) => self.uniform_buffer(set, binding, buffer),
+            Err(e) => {
+                self.error.get_or_insert(e.into());
+                self
+            }
+        }
+    }
+
+    pub fn storage_buffer(self, set: u32, binding: u32, buffer: Arc<dyn BufferAccess>) -> Self {
+        self.bind(set, binding, BindingKind::StorageBuffer, WriteDescriptorSet::buffer(binding, buffer))
+    }
+
+    pub fn image_sampler(
+        self,
+        set: u32,
+        binding: u32,
+        image_view: Arc<dyn ImageViewAbstract>,
+        sampler: Arc<Sampler>,
+    ) -> Self {
+        self.bind(
+            set,
+            binding,
+            BindingKind::ImageSampler,
+            WriteDescriptorSet::image_view_sampler(binding, image_view, sampler),
+        )
+    }
+
+    /// Binds a `texture2D` that is sampled with a separately bound `sampler`.
+    pub fn sampled_image(self, set: u32, binding: u32, image_view: Arc<dyn ImageViewAbstract>) -> Self {
+        self.bind(set, binding, BindingKind::SampledImage, WriteDescriptorSet::image_view(binding, image_view))
+    }
+
+    pub fn sampler(self, set: u32, binding: u32, sampler: Arc<Sampler>) -> Self {
+        self.bind(set, binding, BindingKind::Sampler, WriteDescriptorSet::sampler(binding, sampler))
+    }
+
+    pub fn storage_image(self, set: u32, binding: u32, image_view: Arc<dyn ImageViewAbstract>) -> Self {
+        self.bind(set, binding, BindingKind::StorageImage, WriteDescriptorSet::image_view(binding, image_view))
+    }
+
+    /// Sets the push constants, starting at offset 0. The size of `T` must be a multiple of 4.
+    pub fn push_constants<T: Pod>(mut self, data: T) -> Self {
+        self.push_constants = Some(bytemuck::bytes_of(&data).to_vec());
+        self
+    }
+
+    /// How many descriptor sets `record` creates.
+    pub fn set_count(&self) -> u32 {
+        let mut sets: Vec<u32> = self.bindings.iter().map(|b| b.set).collect();
+        sets.dedup();
+        sets.len() as u32
+    }
+
+    /// Bytes written into buffers created by these bindings.
+    pub fn uploaded(&self) -> usize {
+        self.uploaded
+    }
+
+    /// Checks the bindings and push constants against the layout of the pipeline they will be
+    /// used with.
+    pub fn validate(&self, layout: &PipelineLayout) -> GameResult {
+        let error = |message: String| Err(GameError::BindingError(message));
+
+        for b in &self.bindings {
+            let declared = layout
+                .set_layouts()
+                .get(b.set as usize)
+                .and_then(|set| set.bindings().get(&b.binding));
+
+            match declared {
+                None => return error(format!(
+                    "set {} binding {} is not declared by the shader",
+                    b.set, b.binding,
+                )),
+                Some(declared) if !b.kind.matches(declared.descriptor_type) => return error(format!(
+                    "set {} binding {} is a {:?} in the shader but a {:?} was bound",
+                    b.set, b.binding, declared.descriptor_type, b.kind,
+                )),
+                _ => {}
+            }
+        }
+
+        let mut written: Vec<u32> = self.bindings.iter().map(|b| b.set).collect();
+        written.dedup();
+        for set in written {
+            for binding in layout.set_layouts()[set as usize].bindings().keys() {
+                if !self.bindings.iter().any(|b| b.set == set && b.binding == *binding) {
+                    return error(format!("set {} binding {} is declared by the shader but was not bound", set, binding));
+                }
+            }
+        }
+
+        if let Some(data) = &self.push_constants {
+            let available = layout
+                .push_constant_ranges()
+                .iter()
+                .map(|range| range.offset + range.size)
+                .max()
+                .unwrap_or(0);
+
+            if data.len() as u32 > available {
+                return error(format!(
+                    "{} bytes of push constants were bound but the shader declares {}",
+                    data.len(), available,
+                ));
+            }
+
+            if data.len() % 4 != 0 {
+                return error(format!("push constants must be a multiple of 4 bytes, got {}", data.len()));
+            }
+
+            if data.len() > MAX_PUSH_CONSTANTS {
+                return error(format!(
+                    "at most {} bytes of push constants can be bound, got {}",
+                    MAX_PUSH_CONSTANTS, data.len(),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Validates the bindings, then binds the descriptor sets and pushes the constants.
+    pub fn record<L, P>(
+        self,
+        builder: &mut AutoCommandBufferBuilder<L, P>,
+        bind_point: PipelineBindPoint,
+        layout: Arc<PipelineLayout>,
+    ) -> Result<()> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+
+        self.validate(&layout)?;
+
+        let mut sets: BTreeMap<u32, Vec<WriteDescriptorSet>> = BTreeMap::new();
+        for binding in self.bindings {
+            sets.entry(binding.set).or_default().push(binding.write);
+        }
+
+        for (set, writes) in sets {
+            let descriptor_set = PersistentDescriptorSet::new(
+                layout.set_layouts()[set as usize].clone(),
+                writes,
+            )?;
+
+            builder.bind_descriptor_sets(bind_point, layout.clone(), set, descriptor_set);
+        }
+
+        if let Some(data) = self.push_constants {
+            // vulkano takes the size of the push constants from their type, so the validated
+            // bytes are pushed as a word array of the same size.
+            macro_rules! push {
+                ($($words:literal)*) => {
+                    match data.len() / 4 {
+                        0 => {}
+                        $($words => push_words::<$words, _, _>(builder, layout, &data),)*
+                        _ => unreachable!("push constants are validated to at most {} bytes", MAX_PUSH_CONSTANTS),
+                    }
+                };
+            }
+
+            push!(
+                1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16 17 18 19 20 21 22 23 24 25 26 27 28 29 30 31 32
+                33 34 35 36 37 38 39 40 41 42 43 44 45 46 47 48 49 50 51 52 53 54 55 56 57 58 59 60 61 62 63 64
+            );
+        }
+
+        Ok(())
+    }
+}
+
+// Pushes `data`, `N` words long, as a single range at offset 0.
+fn push_words<const N: usize, L, P>(builder: &mut AutoCommandBufferBuilder<L, P>, layout: Arc<PipelineLayout>, data: &[u8]) {
+    let mut words = [0u32; N];
+    bytemuck::cast_slice_mut(&mut words).copy_from_slice(data);
+    builder.push_constants(layout, 0, words);
+}
@@ -278,6 +278,98 @@ impl Camera for OrthographicCamera {
     }
 }
 
+/// A 2D camera whose units are pixels, or points, of the target with the origin at the top
+/// left corner.
+///
+/// Translations move the camera by that many units, so a world camera can follow the player
+/// in pixels while a second one draws the UI.
+pub struct ScreenCamera {
+    width: f32,
+    height: f32,
+    model: Matrix4<f32>,
+    view: Matrix4<f32>,
+    proj: Matrix4<f32>,
+}
+
+impl ScreenCamera {
+    /// A camera showing `width` by `height` units.
+    pub fn new(width: f32, height: f32) -> Self {
+        let mut camera = Self {
+            width,
+            height,
+            model: Matrix4::identity(),
+            view: Matrix4::identity(),
+            proj: Matrix4::identity(),
+        };
+        camera.resize(width, height);
+
+        camera
+    }
+
+    /// Changes the visible area, keeping the camera's position.
+    pub fn resize(&mut self, width: f32, height: f32) {
+        self.width = width;
+        self.height = height;
+        self.proj = Matrix4::from_translation(Vector3::new(-1.0, -1.0, 0.0))
+            * Matrix4::from_nonuniform_scale(2.0 / width, 2.0 / height, 1.0);
+    }
+
+    pub fn size(&self) -> [f32; 2] {
+        [self.width, self.height]
+    }
+}
+
+impl Camera for ScreenCamera {
+    fn model_array(&self) -> [[f32; 4]; 4] {
+        self.model.into()
+    }
+
+    fn view_array(&self) -> [[f32; 4]; 4] {
+        self.view.into()
+    }
+
+    fn proj_array(&self) -> [[f32; 4]; 4] {
+        self.proj.into()
+    }
+
+    fn mv_array(&self) -> [[f32; 4]; 4] {
+        (self.view * self.model).into()
+    }
+
+    fn mvp_array(&self) -> [[f32; 4]; 4] {
+        self.as_mvp()
+    }
+
+    fn rotate_x(&mut self, degs: Deg<f32>) {
+        self.model = Matrix4::from_angle_x(degs) * self.model;
+    }
+
+    fn rotate_y(&mut self, degs: Deg<f32>) {
+        self.model = Matrix4::from_angle_y(degs) * self.model;
+    }
+
+    fn rotate_z(&mut self, degs: Deg<f32>) {
+        self.model = Matrix4::from_angle_z(degs) * self.model;
+    }
+
+    fn translate_x(&mut self, amount: f32) {
+        self.view = Matrix4::from_translation(Vector3::new(-amount, 0.0, 0.0)) * self.view;
+    }
+
+    fn translate_y(&mut self, amount: f32) {
+        self.view = Matrix4::from_translation(Vector3::new(0.0, -amount, 0.0)) * self.view;
+    }
+
+    fn translate_z(&mut self, amount: f32) {
+        self.view = Matrix4::from_translation(Vector3::new(0.0, 0.0, -amount)) * self.view;
+    }
+
+    fn as_mvp(&self) -> [[f32; 4]; 4] {
+        // Applied to column vectors, so the model transform comes first.
+        (self.proj * self.view * self.model).into()
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct Camera2D {
 
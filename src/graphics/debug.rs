@@ -0,0 +1,109 @@
+use crate::conf::DebugLevel;
+
+use log::{debug, log, warn, Level};
+use std::ffi::CString;
+use std::sync::Arc;
+use vulkano::device::{Device, DeviceOwned};
+use vulkano::instance::debug::{DebugCallback, Message, MessageSeverity, MessageType};
+use vulkano::instance::{layers_list, Instance, InstanceExtensions};
+use vulkano::VulkanObject;
+
+/// The Khronos validation layer, enabled when `Conf` asks for a `DebugLevel` other than `Off`.
+pub const VALIDATION_LAYER: &str = "VK_LAYER_KHRONOS_validation";
+
+/// The layers to create the instance with, the validation layer when it is requested and
+/// installed.
+pub(crate) fn layers(level: DebugLevel) -> Vec<String> {
+    if level == DebugLevel::Off {
+        return Vec::new();
+    }
+
+    let available = layers_list()
+        .map(|mut layers| layers.any(|l| l.name() == VALIDATION_LAYER))
+        .unwrap_or(false);
+
+    if available {
+        vec![VALIDATION_LAYER.to_string()]
+    } else {
+        warn!("{} is not installed, running without validation", VALIDATION_LAYER);
+        Vec::new()
+    }
+}
+
+/// The instance extensions needed for the messenger and object names.
+pub(crate) fn extensions(level: DebugLevel) -> InstanceExtensions {
+    let supported = InstanceExtensions::supported_by_core()
+        .map(|e| e.ext_debug_utils)
+        .unwrap_or(false);
+
+    InstanceExtensions {
+        ext_debug_utils: level != DebugLevel::Off && supported,
+        ..InstanceExtensions::none()
+    }
+}
+
+/// Logs validation messages of `level` and above through the `log` crate, errors as
+/// `log::Level::Error` down to verbose messages as `Debug`. The callback stops when it is
+/// dropped.
+pub(crate) fn messenger(instance: &Arc<Instance>, level: DebugLevel) -> Option<DebugCallback> {
+    let severity = match level {
+        DebugLevel::Off => return None,
+        DebugLevel::Errors => MessageSeverity::errors(),
+        DebugLevel::Warnings => MessageSeverity::errors_and_warnings(),
+        DebugLevel::Info => MessageSeverity {
+            information: true,
+            ..MessageSeverity::errors_and_warnings()
+        },
+        DebugLevel::Verbose => MessageSeverity::all(),
+    };
+
+    match DebugCallback::new(instance, severity, MessageType::all(), log_message) {
+        Ok(callback) => Some(callback),
+        Err(e) => {
+            warn!("Failed to install the Vulkan debug messenger: {:?}", e);
+            None
+        }
+    }
+}
+
+fn log_message(message: &Message) {
+    let level = if message.severity.error {
+        Level::Error
+    } else if message.severity.warning {
+        Level::Warn
+    } else if message.severity.information {
+        Level::Info
+    } else {
+        Level::Debug
+    };
+
+    let ty = if message.ty.validation {
+        "validation"
+    } else if message.ty.performance {
+        "performance"
+    } else {
+        "general"
+    };
+
+    log!(
+        level,
+        "Vulkan {} [{}]: {}",
+        ty,
+        message.layer_prefix.unwrap_or("unknown"),
+        message.description,
+    );
+}
+
+/// Names `object` in validation messages and graphics debugger captures. Does nothing unless
+/// debugging was enabled in `Conf`.
+pub fn set_name<T: VulkanObject + DeviceOwned>(device: &Device, object: &T, name: &str) {
+    if !device.instance().enabled_extensions().ext_debug_utils {
+        return;
+    }
+
+    if let Ok(name) = CString::new(name) {
+        if let Err(e) = device.set_object_name(object, &name) {
+            debug!("Failed to name {}: {:?}", name.to_string_lossy(), e);
+        }
+    }
+}
@@ -0,0 +1,389 @@
+use crate::graphics::camera::{Camera, OrthographicCamera};
+use crate::graphics::render_pass::{frame, RenderPass};
+use crate::graphics::renderer::Renderer;
+use crate::graphics::resources::{FrameResources, RenderStats};
+use crate::graphics::shader::ShaderId;
+use crate::graphics::shapes::{self, DrawMode, Geometry, LineCap, LineJoin, StrokeOptions};
+use crate::graphics::{library, Color, DrawInfo, Rect};
+
+use std::sync::Arc;
+use vulkano::image::ImageViewAbstract;
+use vulkano::sync::GpuFuture;
+
+use anyhow::{anyhow, Result};
+
+type Point = [f32; 2];
+
+/// Immediate mode shapes and labels for visualizing game state while developing.
+///
+/// Everything added during a frame is tessellated into one mesh and drawn in a single draw call,
+/// plus one for `stats`, over the finished scene when the frame ends, then cleared. While disabled the calls do
+/// nothing, so they can be left in place and switched on at runtime.
+///
+/// Shapes are given in world coordinates and drawn with the camera of the last render pass
+/// frame the game started, so they line up with the scene. Only `stats` writes in clip space.
+///
+/// # Examples
+/// ```no_run
+/// use ledge::graphics::{Color, Rect};
+/// use ledge::input::keyboard::KeyCode;
+/// use ledge::interface::Interface;
+///
+/// fn update(interface: &mut Interface, hitbox: Rect, position: [f32; 2], velocity: [f32; 2], enemies: usize) {
+///     let next = [position[0] + velocity[0], position[1] + velocity[1]];
+///
+///     interface.debug_draw.rect(hitbox, Color::red());
+///     interface.debug_draw.arrow(position, next, Color::white());
+///     interface.debug_draw.text([-0.95, -0.95], &format!("enemies: {}", enemies), Color::white());
+///
+///     if interface.keyboard_context.current_pressed == Some(KeyCode::F3) {
+///         interface.debug_draw.toggle();
+///     }
+/// }
+/// ```
+pub struct DebugDraw {
+    enabled: bool,
+    geometry: Geometry,
+    // Shapes in clip space, drawn without the camera.
+    overlay: Geometry,
+    /// Width of every line and outline, in world units.
+    pub line_width: f32,
+    /// Size of one pixel of the label font in world units, glyphs are 3 by 5 pixels.
+    pub text_size: f32,
+    /// Draws the shapes with this camera instead of the scene's.
+    pub camera: Option<Arc<dyn Camera>>,
+    /// Writes the last frame's `RenderStats` in the top left corner every frame.
+    pub stats_overlay: bool,
+    render_pass: RenderPass,
+    shader: ShaderId,
+    resources: Arc<FrameResources>,
+    // Only transforms the frame's own drawables, the shapes are drawn with `mvp`.
+    frame_camera: Arc<dyn Camera>,
+}
+
+impl DebugDraw {
+    pub fn new(renderer: &Renderer) -> Result<Self> {
+        let mut render_pass = RenderPass::new(
+            renderer.queue.clone(),
+            renderer.resources.clone(),
+            vulkano::single_pass_renderpass!(renderer.device.clone(),
+                attachments: {
+                    color: {
+                        load: Load,
+                        store: Store,
+                        format: renderer.output_format(),
+                        samples: 1,
+                    }
+                },
+                pass: {
+                    color: [color],
+                    depth_stencil: {}
+                }
+            )?,
+        )?;
+
+        render_pass.set_name("debug draw");
+        render_pass.set_scene_camera(false);
+        library::register(renderer.device.clone(), &mut render_pass)?;
+        let shader = render_pass
+            .shader_id(library::VERTEX_COLOR)
+            .ok_or_else(|| anyhow!("The shader library has no vertex color shader"))?;
+
+        Ok(Self {
+            enabled: true,
+            geometry: Geometry::new(),
+            overlay: Geometry::new(),
+            line_width: 0.005,
+            text_size: 0.005,
+            camera: None,
+            stats_overlay: false,
+            render_pass,
+            shader,
+            resources: renderer.resources.clone(),
+            frame_camera: Arc::new(OrthographicCamera::default()),
+        })
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.clear();
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.set_enabled(!self.enabled);
+    }
+
+    /// Drops everything added since the last frame.
+    pub fn clear(&mut self) {
+        self.geometry = Geometry::new();
+        self.overlay = Geometry::new();
+    }
+
+    pub fn line(&mut self, from: Point, to: Point, color: Color) {
+        self.polyline(&[from, to], color);
+    }
+
+    pub fn polyline(&mut self, points: &[Point], color: Color) {
+        if self.enabled {
+            self.geometry.append(&shapes::polyline(self.stroke(), points, color));
+        }
+    }
+
+    /// A line from `from` to `to` with a head at `to`.
+    pub fn arrow(&mut self, from: Point, to: Point, color: Color) {
+        if !self.enabled {
+            return;
+        }
+
+        let (dx, dy) = (to[0] - from[0], to[1] - from[1]);
+        let length = (dx * dx + dy * dy).sqrt();
+        if length == 0.0 {
+            return;
+        }
+
+        let head = (self.line_width * 4.0).min(length / 2.0);
+        let (ux, uy) = (dx / length, dy / length);
+        let base = [to[0] - ux * head, to[1] - uy * head];
+        let side = [-uy * head / 2.0, ux * head / 2.0];
+
+        self.line(from, base, color);
+        self.geometry.append(&shapes::polygon(
+            DrawMode::Fill,
+            &[to, [base[0] + side[0], base[1] + side[1]], [base[0] - side[0], base[1] - side[1]]],
+            color,
+        ));
+    }
+
+    pub fn rect(&mut self, rect: Rect, color: Color) {
+        if self.enabled {
+            self.geometry.append(&shapes::rectangle(DrawMode::Stroke(self.stroke()), rect, color));
+        }
+    }
+
+    pub fn fill_rect(&mut self, rect: Rect, color: Color) {
+        if self.enabled {
+            self.geometry.append(&shapes::rectangle(DrawMode::Fill, rect, color));
+        }
+    }
+
+    pub fn circle(&mut self, center: Point, radius: f32, color: Color) {
+        if self.enabled {
+            self.geometry.append(&shapes::circle(DrawMode::Stroke(self.stroke()), center, radius, color));
+        }
+    }
+
+    /// Outlines the cells of a grid with `columns` by `rows` cells of `cell` size starting at `origin`.
+    pub fn grid(&mut self, origin: Point, cell: [f32; 2], columns: u32, rows: u32, color: Color) {
+        if !self.enabled {
+            return;
+        }
+
+        let (width, height) = (cell[0] * columns as f32, cell[1] * rows as f32);
+
+        for column in 0..=columns {
+            let x = origin[0] + cell[0] * column as f32;
+            self.line([x, origin[1]], [x, origin[1] + height], color);
+        }
+        for row in 0..=rows {
+            let y = origin[1] + cell[1] * row as f32;
+            self.line([origin[0], y], [origin[0] + width, y], color);
+        }
+    }
+
+    /// Writes `text` with its top left corner at `position` in a small built-in font.
+    ///
+    /// The font covers digits, letters, which are drawn upper case, and common punctuation.
+    pub fn text(&mut self, position: Point, text: &str, color: Color) {
+        if !self.enabled {
+            return;
+        }
+
+        write_text(&mut self.geometry, self.text_size, position, text, color);
+    }
+
+    /// Writes `stats` as a block of labelled lines with its top left corner at `position` in
+    /// clip space, whatever the camera, with pixels of the font `text_size` units wide.
+    pub fn stats(&mut self, position: Point, stats: &RenderStats, color: Color) {
+        if !self.enabled {
+            return;
+        }
+
+        let text = format!(
+            "draws: {} (saved {})\ninstances: {}\nvertices: {}\npipelines: {}\nsets: {}\nupload: {} kb\ncommand buffers: {}",
+            stats.draw_calls,
+            stats.draws_saved,
+            stats.instances,
+            stats.vertices,
+            stats.pipeline_binds,
+            stats.descriptor_sets,
+            stats.bytes_uploaded / 1024,
+            stats.command_buffers,
+        );
+
+        write_text(&mut self.overlay, self.text_size, position, &text, color);
+    }
+
+    /// Draws and clears everything added this frame over `final_image`.
+    pub fn draw(
+        &mut self,
+        before_future: Box<dyn GpuFuture>,
+        final_image: Arc<dyn ImageViewAbstract>,
+    ) -> Result<Box<dyn GpuFuture>> {
+        if self.geometry.is_empty() && self.overlay.is_empty() {
+            return Ok(before_future);
+        }
+
+        let geometry = std::mem::take(&mut self.geometry);
+        let overlay = std::mem::take(&mut self.overlay);
+        let mvp = match &self.camera {
+            Some(camera) => camera.as_mvp(),
+            None => self
+                .resources
+                .camera_mvp()
+                .unwrap_or_else(|| self.frame_camera.as_mvp()),
+        };
+
+        let mut frame = self.render_pass.frame(
+            Color::transparent().into(),
+            before_future,
+            final_image,
+            self.frame_camera.clone(),
+        )?;
+
+        let mut after_future = None;
+        while let Some(pass) = frame.next_pass()? {
+            after_future = match pass {
+                frame::PassState::DrawPass(mut pass) => {
+                    let layers = [(&geometry, mvp), (&overlay, self.frame_camera.as_mvp())];
+                    for (geometry, mvp) in layers {
+                        if geometry.is_empty() {
+                            continue;
+                        }
+
+                        let data = pass
+                            .pipeline_data()
+                            .vertex_buffer(geometry.vertices.clone())
+                            .indices_u32(geometry.indices.clone())
+                            .instance_buffer(vec![DrawInfo::default().into()])
+                            .uniform(0, 0, mvp);
+                        pass.draw_data(self.shader, data)?;
+                    }
+                    None
+                }
+                frame::PassState::Finished(af) => Some(af),
+            }
+        }
+
+        Ok(after_future.unwrap())
+    }
+
+    fn stroke(&self) -> StrokeOptions {
+        StrokeOptions {
+            width: self.line_width,
+            join: LineJoin::Miter,
+            cap: LineCap::Square,
+        }
+    }
+}
+
+// Appends `text` in the built-in font to `geometry`, with font pixels `size` wide.
+fn write_text(geometry: &mut Geometry, size: f32, position: Point, text: &str, color: Color) {
+    let mut x = position[0];
+    let mut y = position[1];
+
+    for c in text.chars() {
+        if c == '\n' {
+            x = position[0];
+            y += size * 7.0;
+            continue;
+        }
+
+        if let Some(rows) = glyph(c) {
+            for (row, bits) in rows.iter().enumerate() {
+                // One rectangle per run of lit pixels in the row.
+                let mut run_start = None;
+                for (column, bit) in bits.chars().chain(['.']).enumerate() {
+                    match (bit == '#', run_start) {
+                        (true, None) => run_start = Some(column),
+                        (false, Some(start)) => {
+                            let rect = Rect {
+                                x: x + start as f32 * size,
+                                y: y + row as f32 * size,
+                                w: (column - start) as f32 * size,
+                                h: size,
+                            };
+                            geometry.append(&shapes::rectangle(DrawMode::Fill, rect, color));
+                            run_start = None;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        x += size * 4.0;
+    }
+}
+
+// 3 by 5 pixel glyphs, rows from the top.
+fn glyph(c: char) -> Option<[&'static str; 5]> {
+    Some(match c.to_ascii_uppercase() {
+        ' ' => ["...", "...", "...", "...", "..."],
+        '0' => ["###", "#.#", "#.#", "#.#", "###"],
+        '1' => [".#.", "##.", ".#.", ".#.", "###"],
+        '2' => ["###", "..#", "###", "#..", "###"],
+        '3' => ["###", "..#", ".##", "..#", "###"],
+        '4' => ["#.#", "#.#", "###", "..#", "..#"],
+        '5' => ["###", "#..", "###", "..#", "###"],
+        '6' => ["###", "#..", "###", "#.#", "###"],
+        '7' => ["###", "..#", "..#", ".#.", ".#."],
+        '8' => ["###", "#.#", "###", "#.#", "###"],
+        '9' => ["###", "#.#", "###", "..#", "###"],
+        'A' => [".#.", "#.#", "###", "#.#", "#.#"],
+        'B' => ["##.", "#.#", "##.", "#.#", "##."],
+        'C' => [".##", "#..", "#..", "#..", ".##"],
+        'D' => ["##.", "#.#", "#.#", "#.#", "##."],
+        'E' => ["###", "#..", "##.", "#..", "###"],
+        'F' => ["###", "#..", "##.", "#..", "#.."],
+        'G' => [".##", "#..", "#.#", "#.#", ".##"],
+        'H' => ["#.#", "#.#", "###", "#.#", "#.#"],
+        'I' => ["###", ".#.", ".#.", ".#.", "###"],
+        'J' => ["..#", "..#", "..#", "#.#", ".#."],
+        'K' => ["#.#", "#.#", "##.", "#.#", "#.#"],
+        'L' => ["#..", "#..", "#..", "#..", "###"],
+        'M' => ["#.#", "###", "###", "#.#", "#.#"],
+        'N' => ["##.", "#.#", "#.#", "#.#", "#.#"],
+        'O' => [".#.", "#.#", "#.#", "#.#", ".#."],
+        'P' => ["##.", "#.#", "##.", "#..", "#.."],
+        'Q' => [".#.", "#.#", "#.#", "##.", ".##"],
+        'R' => ["##.", "#.#", "##.", "#.#", "#.#"],
+        'S' => [".##", "#..", ".#.", "..#", "##."],
+        'T' => ["###", ".#.", ".#.", ".#.", ".#."],
+        'U' => ["#.#", "#.#", "#.#", "#.#", "###"],
+        'V' => ["#.#", "#.#", "#.#", "#.#", ".#."],
+        'W' => ["#.#", "#.#", "###", "###", "#.#"],
+        'X' => ["#.#", "#.#", ".#.", "#.#", "#.#"],
+        'Y' => ["#.#", "#.#", ".#.", ".#.", ".#."],
+        'Z' => ["###", "..#", ".#.", "#..", "###"],
+        '.' => ["...", "...", "...", "...", ".#."],
+        ',' => ["...", "...", "...", ".#.", "#.."],
+        ':' => ["...", ".#.", "...", ".#.", "..."],
+        '-' => ["...", "...", "###", "...", "..."],
+        '+' => ["...", ".#.", "###", ".#.", "..."],
+        '=' => ["...", "###", "...", "###", "..."],
+        '_' => ["...", "...", "...", "...", "###"],
+        '/' => ["..#", "..#", ".#.", "#..", "#.."],
+        '(' => [".#.", "#..", "#..", "#..", ".#."],
+        ')' => [".#.", "..#", "..#", "..#", ".#."],
+        '!' => [".#.", ".#.", ".#.", "...", ".#."],
+        '?' => ["##.", "..#", ".#.", "...", ".#."],
+        '%' => ["#.#", "..#", ".#.", "#..", "#.#"],
+        _ => return None,
+    })
+}
@@ -0,0 +1,128 @@
+use crate::error::{GameError, GameResult};
+use shaderc::{CompileOptions, Compiler, EnvVersion, ShaderKind, TargetEnv};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShaderStage {
+    Vertex,
+    Fragment,
+    Compute,
+}
+
+/// Compiles GLSL source into SPIR-V words, `file` is only used to report errors.
+///
+/// Shaders are compiled with shaderc, the compiler the built-in shaders in `graphics/shaders`
+/// are built with ahead of time, so copies of them can be edited and compiled here as they
+/// are. Errors report the line of the first error, glslang gives no column.
+pub fn compile(source: &str, stage: ShaderStage, file: &str) -> GameResult<Vec<u32>> {
+    // shaderc takes the source as a C string.
+    if let Some(offset) = source.find('\0') {
+        let line = source[..offset].matches('\n').count() as u32 + 1;
+        return Err(compile_error(file, line, "unexpected NUL character".to_string()));
+    }
+
+    let mut compiler = Compiler::new()
+        .ok_or_else(|| compile_error(file, 0, "failed to start the shader compiler".to_string()))?;
+    let mut options = CompileOptions::new()
+        .ok_or_else(|| compile_error(file, 0, "failed to create the compile options".to_string()))?;
+    options.set_target_env(TargetEnv::Vulkan, EnvVersion::Vulkan1_0 as u32);
+
+    let kind = match stage {
+        ShaderStage::Vertex => ShaderKind::Vertex,
+        ShaderStage::Fragment => ShaderKind::Fragment,
+        ShaderStage::Compute => ShaderKind::Compute,
+    };
+
+    compiler
+        .compile_into_spirv(source, kind, file, "main", Some(&options))
+        .map(|artifact| artifact.as_binary().to_vec())
+        .map_err(|error| match error {
+            shaderc::Error::CompilationError(_, messages) => parse_error(file, &messages),
+            error => compile_error(file, 0, error.to_string()),
+        })
+}
+
+// Turns the first message of glslang's `file:line: error: message` output into an error.
+fn parse_error(file: &str, messages: &str) -> GameError {
+    let first = messages.lines().next().unwrap_or_default();
+
+    let located = first
+        .strip_prefix(file)
+        .and_then(|rest| rest.strip_prefix(':'))
+        .and_then(|rest| rest.split_once(':'))
+        .and_then(|(line, message)| Some((line.trim().parse().ok()?, message.trim())));
+
+    match located {
+        Some((line, message)) => compile_error(file, line, message.to_string()),
+        None => compile_error(file, 0, first.trim().to_string()),
+    }
+}
+
+fn compile_error(file: &str, line: u32, message: String) -> GameError {
+    GameError::ShaderCompileError {
+        file: file.to_string(),
+        line,
+        column: 0,
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compiles_the_texture_shaders() {
+        let vertex = compile(include_str!("shaders/texture.vert"), ShaderStage::Vertex, "texture.vert").unwrap();
+        let fragment = compile(include_str!("shaders/texture.frag"), ShaderStage::Fragment, "texture.frag").unwrap();
+
+        assert_eq!(vertex[0], 0x0723_0203);
+        assert_eq!(fragment[0], 0x0723_0203);
+    }
+
+    #[test]
+    fn compiles_every_built_in_shader() {
+        let shaders = [
+            ("color.vert", ShaderStage::Vertex, include_str!("shaders/color.vert")),
+            ("color.frag", ShaderStage::Fragment, include_str!("shaders/color.frag")),
+            ("solid.vert", ShaderStage::Vertex, include_str!("shaders/solid.vert")),
+            ("text.frag", ShaderStage::Fragment, include_str!("shaders/text.frag")),
+            ("texture_array.vert", ShaderStage::Vertex, include_str!("shaders/texture_array.vert")),
+            ("texture_array.frag", ShaderStage::Fragment, include_str!("shaders/texture_array.frag")),
+            ("vignette.frag", ShaderStage::Fragment, include_str!("shaders/vignette.frag")),
+            ("scanlines.frag", ShaderStage::Fragment, include_str!("shaders/scanlines.frag")),
+            ("quantize.frag", ShaderStage::Fragment, include_str!("shaders/quantize.frag")),
+        ];
+
+        for (file, stage, source) in shaders {
+            if let Err(e) = compile(source, stage, file) {
+                panic!("{}", e);
+            }
+        }
+    }
+
+    #[test]
+    fn reports_the_error_line() {
+        let source = "#version 450\n\nvoid main() {\n    undeclared = 1.0;\n}\n";
+
+        match compile(source, ShaderStage::Fragment, "broken.frag") {
+            Err(GameError::ShaderCompileError { file, line, .. }) => {
+                assert_eq!(file, "broken.frag");
+                assert_eq!(line, 4);
+            }
+            result => panic!("expected a compile error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn parses_glslang_messages() {
+        let error = parse_error("shader.glsl", "shader.glsl:2: error: '#error' : one\nshader.glsl:3: error: '#error' : two\n");
+
+        match error {
+            GameError::ShaderCompileError { line, column, message, .. } => {
+                assert_eq!((line, column), (2, 0));
+                assert_eq!(message, "error: '#error' : one");
+            }
+            error => panic!("unexpected error {:?}", error),
+        }
+    }
+}
@@ -10,12 +10,13 @@ use std::io::Cursor;
 use std::io::Read;
 use std::path;
 use std::sync::Arc;
-use vulkano::descriptor_set::PersistentDescriptorSet;
 use vulkano::format::Format;
-use vulkano::image::{view::ImageView, ImageDimensions, ImmutableImage, MipmapsCount};
-use vulkano::command_buffer::CommandBufferUsage;
-use vulkano::pipeline::graphics::viewport::Viewport;
+use vulkano::image::view::{ImageView, ImageViewCreateInfo, ImageViewType};
+use vulkano::image::{ImageDimensions, ImmutableImage, MipmapsCount};
 use vulkano::pipeline::{Pipeline, PipelineBindPoint};
+use vulkano::sync::GpuFuture;
+
+use anyhow::anyhow;
 
 #[derive(Clone)]
 #[allow(unused)]
@@ -27,44 +28,60 @@ pub struct Image {
 }
 
 impl Image {
-    pub fn new<P: AsRef<path::Path>>(queue: Arc<Queue>, sampler: Arc<Sampler>, path: P) -> Self {
-        let mut png_bytes = Vec::new();
-
-        fs::File::open(path)
-            .unwrap()
-            .read_to_end(&mut png_bytes)
-            .unwrap();
-
-        let cursor = Cursor::new(png_bytes);
-        let decoder = png::Decoder::new(cursor);
-        let mut reader = decoder.read_info().unwrap();
-        let width = reader.info().width;
-        let height = reader.info().height;
+    /// Loads a PNG and blocks until it is on the GPU. `Uploader::load_image` loads without
+    /// stalling the frame.
+    pub fn new<P: AsRef<path::Path>>(queue: Arc<Queue>, sampler: Arc<Sampler>, path: P) -> Result<Self> {
+        let (width, height, image_data) = read_png(path)?;
+        let (image, upload) = Self::from_rgba(queue, sampler, width, height, image_data)?;
+        upload.then_signal_fence_and_flush()?.wait(None)?;
+
+        Ok(image)
+    }
+
+    /// Submits 8 bit RGBA pixels to `queue`, returning the image and the future of its upload.
+    /// Nothing may read the image until the future is complete, join it into the future of
+    /// the frame that first draws the image.
+    pub fn from_rgba(
+        queue: Arc<Queue>,
+        sampler: Arc<Sampler>,
+        width: u32,
+        height: u32,
+        data: Vec<u8>,
+    ) -> Result<(Self, Box<dyn GpuFuture>)> {
+        if data.len() != (width * height * 4) as usize {
+            return Err(anyhow!("Expected {}x{} RGBA pixels, got {} bytes", width, height, data.len()));
+        }
+
         let dimensions = ImageDimensions::Dim2d {
-            width: width,
-            height: height,
+            width,
+            height,
             array_layers: 1,
         };
-        let mut image_data = Vec::new();
-        image_data.resize((width * height * 8) as usize, 0);
-        reader.next_frame(&mut image_data).unwrap();
 
-        let (image, _) = ImmutableImage::from_iter(
-            image_data.iter().cloned(),
+        let (image, upload) = ImmutableImage::from_iter(
+            data,
             dimensions,
             MipmapsCount::One,
             Format::R8G8B8A8_UNORM,
-            queue.clone(),
-        )
-        .unwrap();
-        let image_view = ImageView::new_default(image).unwrap();
+            queue,
+        )?;
 
-        Self {
-            inner: image_view,
+        let image = Self {
+            inner: ImageView::new_default(image)?,
             width,
             height,
             sampler,
-        }
+        };
+
+        Ok((image, upload.boxed()))
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
     }
 
     // pub fn with_size(queue: Arc<Queue>, w: usize, h: usize) -> Self {
@@ -139,75 +156,41 @@ impl Image {
 }
 
 impl Drawable for Image {
-    fn draw(&self, queue: Arc<Queue>, shader_handle: &Box<dyn ShaderHandle>, info: DrawInfo) -> Result<SecondaryAutoCommandBuffer> {
-        let mut builder = AutoCommandBufferBuilder::secondary_graphics(
-            queue.device().clone(),
-            queue.family(),
-            CommandBufferUsage::MultipleSubmit,
-            shader_handle.pipeline().subpass().clone(),
-        )?;
+    fn draw(&self, context: &DrawContext, info: DrawInfo) -> Result<SecondaryAutoCommandBuffer> {
+        self.draw_instanced(context, &[info.into()])
+    }
+
+    fn batch_key(&self) -> Option<BatchKey> {
+        Some([
+            Arc::as_ptr(&self.inner) as usize,
+            Arc::as_ptr(&self.sampler) as usize,
+            0,
+        ])
+    }
+
+    fn draw_instanced(&self, context: &DrawContext, instances: &[InstanceData]) -> Result<SecondaryAutoCommandBuffer> {
+        let resources = context.resources;
+        let mut builder = context.command_buffer()?;
 
         let vertex_count = QUAD_VERTICES.len() as u32;
-        let vertex_buffer = CpuAccessibleBuffer::from_iter(
-            queue.device().clone(),
-            BufferUsage::all(),
-            false,
-            QUAD_VERTICES.to_vec(),
-        ).unwrap();
-
-        let instances: Vec<InstanceData> = vec![info.into()];
+        let vertex_buffer = resources.quad();
+
         let instance_count = instances.len() as u32;
-        let instance_buffer = CpuAccessibleBuffer::from_iter(
-            queue.device().clone(),
-            BufferUsage::all(),
-            false,
-            instances,
-        ).unwrap();
-
-        let layout = shader_handle.layout()[1].clone();
-
-        let set = PersistentDescriptorSet::new(
-            layout.clone(),
-            [WriteDescriptorSet::image_view_sampler(
-                0,
-                self.inner.clone(),
-                self.sampler.clone(),
-            )],
-        ).unwrap();
-
-        let layout = shader_handle.layout()[0].clone();
-
-        let mvp_buffer = CpuAccessibleBuffer::from_iter(
-            queue.device().clone(),
-            BufferUsage::all(),
-            false,
-            [
-                    [1.0,0.0,0.0,0.0],
-                    [0.0,1.0,0.0,0.0],
-                    [0.0,0.0,1.0,0.0],
-                    [0.0,0.0,0.0,1.0],
-                ],
-        ).unwrap();
-
-        let cam_set = PersistentDescriptorSet::new(
-            layout.clone(),
-            [WriteDescriptorSet::buffer(
-                0,
-                mvp_buffer,
-            )],
-        ).unwrap();
+        let instance_buffer = resources.instances(instances.iter().cloned())?;
+
+        let set = resources.texture_set(
+            context.set_layout(1)?,
+            self.inner.clone(),
+            self.sampler.clone(),
+        )?;
+
+        let cam_set = context.camera_set()?;
 
         builder
-            .bind_pipeline_graphics(shader_handle.pipeline().clone())
-            .set_viewport(0, vec![Viewport {
-                origin: [0.0, 0.0],
-                dimensions: [800 as f32, 600 as f32],
-                depth_range: 0.0..1.0,
-            }])
             .bind_vertex_buffers(0, (vertex_buffer, instance_buffer))
             .bind_descriptor_sets(
                 PipelineBindPoint::Graphics,
-                shader_handle.pipeline().layout().clone(),
+                context.shader.pipeline().layout().clone(),
                 0,
                 (cam_set, set),
             )
@@ -216,11 +199,178 @@ impl Drawable for Image {
                 instance_count, 
                 0, 
                 0, 
-                )
-            .unwrap();
+                )?;
+        resources.record_vertices(vertex_count, instance_count);
         
         let commands = builder.build()?;
 
         Ok(commands)
     }
 }
+
+/// Several images of the same size stored as the layers of one texture array.
+///
+/// Every instance of a draw picks its image with `DrawInfo::layer`, so sprites using different
+/// images of the array are still merged into one draw by `Pass::draw_with`. Draw it with the
+/// `library::SPRITE_ARRAY` shader.
+#[derive(Clone)]
+pub struct TextureArray {
+    inner: Arc<ImageView<ImmutableImage>>,
+    width: u32,
+    height: u32,
+    layers: u32,
+    sampler: Arc<Sampler>,
+}
+
+impl TextureArray {
+    /// Loads one layer per path, every image must be an RGBA PNG with the same dimensions.
+    pub fn new<P: AsRef<path::Path>>(queue: Arc<Queue>, sampler: Arc<Sampler>, paths: &[P]) -> Result<Self> {
+        if paths.is_empty() {
+            return Err(anyhow!("A texture array needs at least one image"));
+        }
+
+        let mut width = 0;
+        let mut height = 0;
+        let mut image_data = Vec::new();
+
+        for (i, path) in paths.iter().enumerate() {
+            let (w, h, data) = read_png(path)?;
+
+            if i == 0 {
+                width = w;
+                height = h;
+            } else if (w, h) != (width, height) {
+                return Err(anyhow!(
+                    "{} is {}x{} but the texture array is {}x{}",
+                    path.as_ref().display(), w, h, width, height,
+                ));
+            }
+
+            if data.len() != (w * h * 4) as usize {
+                return Err(anyhow!("{} is not an 8 bit RGBA image", path.as_ref().display()));
+            }
+
+            image_data.extend(data);
+        }
+
+        let layers = paths.len() as u32;
+        let dimensions = ImageDimensions::Dim2d {
+            width,
+            height,
+            array_layers: layers,
+        };
+
+        let (image, upload) = ImmutableImage::from_iter(
+            image_data,
+            dimensions,
+            MipmapsCount::One,
+            Format::R8G8B8A8_UNORM,
+            queue,
+        )?;
+        upload.then_signal_fence_and_flush()?.wait(None)?;
+
+        // A single layer would get a 2D view by default, the shader samples an array.
+        let view_info = ImageViewCreateInfo {
+            view_type: ImageViewType::Dim2dArray,
+            ..ImageViewCreateInfo::from_image(&image)
+        };
+
+        Ok(Self {
+            inner: ImageView::new(image, view_info)?,
+            width,
+            height,
+            layers,
+            sampler,
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn layers(&self) -> u32 {
+        self.layers
+    }
+
+    pub fn inner(&self) -> &Arc<ImageView<ImmutableImage>> {
+        &self.inner
+    }
+}
+
+impl Drawable for TextureArray {
+    fn draw(&self, context: &DrawContext, info: DrawInfo) -> Result<SecondaryAutoCommandBuffer> {
+        self.draw_instanced(context, &[info.into()])
+    }
+
+    fn batch_key(&self) -> Option<BatchKey> {
+        Some([
+            Arc::as_ptr(&self.inner) as usize,
+            Arc::as_ptr(&self.sampler) as usize,
+            0,
+        ])
+    }
+
+    fn draw_instanced(&self, context: &DrawContext, instances: &[InstanceData]) -> Result<SecondaryAutoCommandBuffer> {
+        let resources = context.resources;
+        let mut builder = context.command_buffer()?;
+
+        let set = resources.texture_set(
+            context.set_layout(1)?,
+            self.inner.clone(),
+            self.sampler.clone(),
+        )?;
+
+        let cam_set = context.camera_set()?;
+
+        builder
+            .bind_vertex_buffers(0, (resources.quad(), resources.instances(instances.iter().cloned())?))
+            .bind_descriptor_sets(
+                PipelineBindPoint::Graphics,
+                context.shader.pipeline().layout().clone(),
+                0,
+                (cam_set, set),
+            )
+            .draw(QUAD_VERTICES.len() as u32, instances.len() as u32, 0, 0)?;
+        resources.record_vertices(QUAD_VERTICES.len() as u32, instances.len() as u32);
+
+        Ok(builder.build()?)
+    }
+}
+
+// Decodes a PNG of any color type into its width, height and 8 bit RGBA pixels.
+pub(crate) fn read_png<P: AsRef<path::Path>>(path: P) -> Result<(u32, u32, Vec<u8>)> {
+    let mut png_bytes = Vec::new();
+
+    fs::File::open(path)?.read_to_end(&mut png_bytes)?;
+
+    let cursor = Cursor::new(png_bytes);
+    let mut decoder = png::Decoder::new(cursor);
+    // Palettes and transparency chunks become color and alpha channels, 16 bit channels 8 bit.
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info()?;
+    let mut image_data = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut image_data)?;
+    image_data.truncate(info.buffer_size());
+
+    let rgba = match info.color_type {
+        png::ColorType::Rgba => image_data,
+        png::ColorType::Rgb => image_data
+            .chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => image_data
+            .chunks_exact(2)
+            .flat_map(|p| [p[0], p[0], p[0], p[1]])
+            .collect(),
+        png::ColorType::Grayscale => image_data.iter().flat_map(|&g| [g, g, g, 255]).collect(),
+        png::ColorType::Indexed => {
+            return Err(anyhow!("Indexed PNG was not expanded to RGB"));
+        }
+    };
+
+    Ok((info.width, info.height, rgba))
+}
@@ -0,0 +1,89 @@
+use crate::graphics::render_pass::RenderPass;
+use crate::graphics::shader::{Shader, ShaderId, VertexTopology};
+use crate::graphics::{fs, vs, InstanceData, Vertex};
+
+use std::sync::Arc;
+use vulkano::device::Device;
+use vulkano::pipeline::graphics::vertex_input::BuffersDefinition;
+
+use anyhow::Result;
+
+/// Textured quads, `Image` draws with this by default.
+pub const SPRITE: &str = "sprite";
+/// Textured quads sampling the layer of a `TextureArray` chosen by each instance.
+pub const SPRITE_ARRAY: &str = "sprite_array";
+/// Quads that use the texture only as an alpha mask, for glyph atlases.
+pub const TEXT: &str = "text";
+/// Triangles filled with the instance color, vertex colors are ignored.
+pub const SOLID: &str = "solid";
+/// Triangles colored by their vertices, tinted by the instance color.
+pub const VERTEX_COLOR: &str = "vertex_color";
+pub const LINES: &str = "lines";
+pub const LINE_STRIP: &str = "line_strip";
+pub const POINTS: &str = "points";
+
+mod color_vs {
+    vulkano_shaders::shader! { ty: "vertex", path: "src/graphics/shaders/color.vert", }
+}
+
+mod solid_vs {
+    vulkano_shaders::shader! { ty: "vertex", path: "src/graphics/shaders/solid.vert", }
+}
+
+mod color_fs {
+    vulkano_shaders::shader! { ty: "fragment", path: "src/graphics/shaders/color.frag", }
+}
+
+mod text_fs {
+    vulkano_shaders::shader! { ty: "fragment", path: "src/graphics/shaders/text.frag", }
+}
+
+mod array_vs {
+    vulkano_shaders::shader! { ty: "vertex", path: "src/graphics/shaders/texture_array.vert", }
+}
+
+mod array_fs {
+    vulkano_shaders::shader! { ty: "fragment", path: "src/graphics/shaders/texture_array.frag", }
+}
+
+/// The vertex layout every built-in shader reads, a `Vertex` buffer followed by an
+/// `InstanceData` buffer.
+pub fn vertex_definition() -> BuffersDefinition {
+    BuffersDefinition::new()
+        .vertex::<Vertex>()
+        .instance::<InstanceData>()
+}
+
+/// Registers every built-in shader on `render_pass` under its name, returning the id of `SPRITE`.
+pub fn register(device: Arc<Device>, render_pass: &mut RenderPass) -> Result<ShaderId> {
+    let texture_vs = vs::load(device.clone())?;
+    let texture_fs = fs::load(device.clone())?;
+    let color_vs = color_vs::load(device.clone())?;
+    let color_fs = color_fs::load(device.clone())?;
+
+    let programs = [
+        (SPRITE, texture_vs.clone(), texture_fs, VertexTopology::TriangleStrip),
+        (TEXT, texture_vs, text_fs::load(device.clone())?, VertexTopology::TriangleStrip),
+        (SPRITE_ARRAY, array_vs::load(device.clone())?, array_fs::load(device.clone())?, VertexTopology::TriangleStrip),
+        (SOLID, solid_vs::load(device)?, color_fs.clone(), VertexTopology::TriangleList),
+        (VERTEX_COLOR, color_vs.clone(), color_fs.clone(), VertexTopology::TriangleList),
+        (LINES, color_vs.clone(), color_fs.clone(), VertexTopology::LineList),
+        (LINE_STRIP, color_vs.clone(), color_fs.clone(), VertexTopology::LineStrip),
+        (POINTS, color_vs, color_fs, VertexTopology::PointList),
+    ];
+
+    let mut sprite = 0;
+    for (name, vertex, fragment, topology) in programs {
+        let id = render_pass.register_named_shader(
+            name,
+            Arc::new(Shader::new(vertex, fragment, topology)),
+            vertex_definition(),
+        )?;
+
+        if name == SPRITE {
+            sprite = id;
+        }
+    }
+
+    Ok(sprite)
+}
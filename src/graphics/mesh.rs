@@ -0,0 +1,275 @@
+use crate::graphics::{BatchKey, DrawContext, Drawable, DrawInfo, InstanceData, Vertex};
+
+use bytemuck::Pod;
+use std::ops::Range;
+use std::sync::Arc;
+use vulkano::buffer::{BufferContents, BufferUsage, CpuAccessibleBuffer, TypedBufferAccess};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, SecondaryAutoCommandBuffer};
+use vulkano::device::Device;
+use vulkano::image::view::ImageViewAbstract;
+use vulkano::pipeline::graphics::vertex_input::{self, BuffersDefinition};
+use vulkano::pipeline::{Pipeline, PipelineBindPoint};
+use vulkano::sampler::Sampler;
+
+use anyhow::{anyhow, Result};
+
+/// The indices of a mesh, in either of the index types Vulkan supports.
+#[derive(Clone)]
+pub enum IndexBuffer {
+    U16(Arc<CpuAccessibleBuffer<[u16]>>),
+    U32(Arc<CpuAccessibleBuffer<[u32]>>),
+}
+
+impl IndexBuffer {
+    pub fn u16(device: Arc<Device>, indices: Vec<u16>) -> Result<Self> {
+        Ok(IndexBuffer::U16(CpuAccessibleBuffer::from_iter(
+            device,
+            BufferUsage::index_buffer(),
+            true,
+            indices,
+        )?))
+    }
+
+    pub fn u32(device: Arc<Device>, indices: Vec<u32>) -> Result<Self> {
+        Ok(IndexBuffer::U32(CpuAccessibleBuffer::from_iter(
+            device,
+            BufferUsage::index_buffer(),
+            true,
+            indices,
+        )?))
+    }
+
+    pub fn len(&self) -> u32 {
+        match self {
+            IndexBuffer::U16(buffer) => buffer.len() as u32,
+            IndexBuffer::U32(buffer) => buffer.len() as u32,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub(crate) fn bind(&self, builder: &mut AutoCommandBufferBuilder<SecondaryAutoCommandBuffer>) {
+        match self {
+            IndexBuffer::U16(buffer) => builder.bind_index_buffer(buffer.clone()),
+            IndexBuffer::U32(buffer) => builder.bind_index_buffer(buffer.clone()),
+        };
+    }
+}
+
+/// Vertices, optionally indexed and textured, drawn with a shader registered with
+/// `Mesh::<V>::vertex_definition()`.
+///
+/// The vertex type can be any type implementing vulkano's `Vertex` trait through `impl_vertex!`,
+/// the shader receives it in the first buffer and the draw's `InstanceData` in the second. The
+/// vertices can be changed in place with `update`, and `slice` draws part of the mesh.
+///
+/// When the shader declares `set = 1` the mesh texture is bound there at binding 0.
+///
+/// # Examples
+/// ```ignore
+/// let terrain = Arc::new(Mesh::indexed_u32(renderer.device.clone(), vertices, indices)?);
+/// let id = render_pass.register_shader(shader, Mesh::<Vertex>::vertex_definition())?;
+///
+/// // Later, after deforming the terrain:
+/// terrain.update(first_changed, &changed_vertices)?;
+/// pass.draw_with(terrain.clone(), id, DrawInfo::default())?;
+/// ```
+pub struct Mesh<V = Vertex>
+where
+    [V]: BufferContents,
+{
+    vertices: Arc<CpuAccessibleBuffer<[V]>>,
+    indices: Option<IndexBuffer>,
+    texture: Option<(Arc<dyn ImageViewAbstract>, Arc<Sampler>)>,
+}
+
+impl<V: vertex_input::Vertex + Pod> Mesh<V> {
+    pub fn new(device: Arc<Device>, vertices: Vec<V>) -> Result<Self> {
+        Ok(Self {
+            vertices: CpuAccessibleBuffer::from_iter(
+                device,
+                BufferUsage::vertex_buffer(),
+                true,
+                vertices,
+            )?,
+            indices: None,
+            texture: None,
+        })
+    }
+
+    pub fn indexed_u16(device: Arc<Device>, vertices: Vec<V>, indices: Vec<u16>) -> Result<Self> {
+        let indices = IndexBuffer::u16(device.clone(), indices)?;
+
+        Ok(Self {
+            indices: Some(indices),
+            ..Self::new(device, vertices)?
+        })
+    }
+
+    pub fn indexed_u32(device: Arc<Device>, vertices: Vec<V>, indices: Vec<u32>) -> Result<Self> {
+        let indices = IndexBuffer::u32(device.clone(), indices)?;
+
+        Ok(Self {
+            indices: Some(indices),
+            ..Self::new(device, vertices)?
+        })
+    }
+
+    pub fn with_texture(mut self, image: Arc<dyn ImageViewAbstract>, sampler: Arc<Sampler>) -> Self {
+        self.texture = Some((image, sampler));
+        self
+    }
+
+    /// The vertex layout shaders drawing this mesh must be registered with.
+    pub fn vertex_definition() -> BuffersDefinition {
+        BuffersDefinition::new()
+            .vertex::<V>()
+            .instance::<InstanceData>()
+    }
+
+    pub fn vertex_count(&self) -> u32 {
+        self.vertices.len() as u32
+    }
+
+    pub fn indices(&self) -> Option<&IndexBuffer> {
+        self.indices.as_ref()
+    }
+
+    /// How many elements a full draw uses, the index count for indexed meshes and the vertex
+    /// count otherwise.
+    pub fn element_count(&self) -> u32 {
+        self.indices.as_ref().map_or(self.vertex_count(), |i| i.len())
+    }
+
+    /// Overwrites the vertices starting at `offset`.
+    ///
+    /// Fails if the range is outside the mesh or the GPU is still reading the vertices.
+    pub fn update(&self, offset: usize, vertices: &[V]) -> Result<()> {
+        let mut contents = self.vertices.write()?;
+
+        let end = offset + vertices.len();
+        if end > contents.len() {
+            return Err(anyhow!(
+                "Cannot update vertices {}..{} of a mesh with {} vertices",
+                offset, end, contents.len(),
+            ));
+        }
+
+        contents[offset..end].copy_from_slice(vertices);
+
+        Ok(())
+    }
+
+    /// A drawable for part of the mesh, `range` counts indices for indexed meshes and vertices
+    /// otherwise.
+    pub fn slice(self: &Arc<Self>, range: Range<u32>) -> Arc<MeshSlice<V>> {
+        Arc::new(MeshSlice {
+            mesh: self.clone(),
+            range,
+        })
+    }
+
+    fn record(
+        &self,
+        context: &DrawContext,
+        instances: &[InstanceData],
+        range: Range<u32>,
+    ) -> Result<SecondaryAutoCommandBuffer> {
+        if range.end > self.element_count() || range.start > range.end {
+            return Err(anyhow!(
+                "Cannot draw elements {}..{} of a mesh with {}",
+                range.start, range.end, self.element_count(),
+            ));
+        }
+
+        let (resources, shader_handle) = (context.resources, context.shader);
+        let mut builder = context.command_buffer()?;
+        let cam_set = context.camera_set()?;
+
+        builder
+            .bind_vertex_buffers(0, (self.vertices.clone(), resources.instances(instances.iter().cloned())?))
+            .bind_descriptor_sets(
+                PipelineBindPoint::Graphics,
+                shader_handle.pipeline().layout().clone(),
+                0,
+                cam_set,
+            );
+
+        if let Some(layout) = shader_handle.layout().get(1) {
+            let (image, sampler) = self
+                .texture
+                .clone()
+                .ok_or_else(|| anyhow!("The shader samples a texture but the mesh has none"))?;
+
+            builder.bind_descriptor_sets(
+                PipelineBindPoint::Graphics,
+                shader_handle.pipeline().layout().clone(),
+                1,
+                resources.texture_set(layout.clone(), image, sampler)?,
+            );
+        }
+
+        let count = range.end - range.start;
+        let instance_count = instances.len() as u32;
+
+        match &self.indices {
+            Some(indices) => {
+                indices.bind(&mut builder);
+                builder.draw_indexed(count, instance_count, range.start, 0, 0)?;
+            }
+            None => {
+                builder.draw(count, instance_count, range.start, 0)?;
+            }
+        }
+        resources.record_vertices(count, instance_count);
+
+        Ok(builder.build()?)
+    }
+}
+
+impl<V: vertex_input::Vertex + Pod> Drawable for Mesh<V> {
+    fn draw(&self, context: &DrawContext, info: DrawInfo) -> Result<SecondaryAutoCommandBuffer> {
+        self.draw_instanced(context, &[info.into()])
+    }
+
+    fn batch_key(&self) -> Option<BatchKey> {
+        let (image, sampler) = self.texture.as_ref().map_or((0, 0), |(image, sampler)| {
+            (
+                Arc::as_ptr(image) as *const () as usize,
+                Arc::as_ptr(sampler) as usize,
+            )
+        });
+
+        Some([Arc::as_ptr(&self.vertices) as *const () as usize, image, sampler])
+    }
+
+    fn draw_instanced(&self, context: &DrawContext, instances: &[InstanceData]) -> Result<SecondaryAutoCommandBuffer> {
+        self.record(context, instances, 0..self.element_count())
+    }
+}
+
+/// Part of a `Mesh`, created with `Mesh::slice`.
+pub struct MeshSlice<V = Vertex>
+where
+    [V]: BufferContents,
+{
+    mesh: Arc<Mesh<V>>,
+    range: Range<u32>,
+}
+
+impl<V> MeshSlice<V>
+where
+    [V]: BufferContents,
+{
+    pub fn range(&self) -> Range<u32> {
+        self.range.clone()
+    }
+}
+
+impl<V: vertex_input::Vertex + Pod> Drawable for MeshSlice<V> {
+    fn draw(&self, context: &DrawContext, info: DrawInfo) -> Result<SecondaryAutoCommandBuffer> {
+        self.mesh.record(context, &[info.into()], self.range.clone())
+    }
+}
@@ -11,12 +11,37 @@ pub mod image;
 /// The shader module defines types, traits, and structs to abstract complex operations that involve shaders.
 /// This module has a lot of intense types from Vulkano wrapped in less scary interfaces that are not as troublesome to deal with
 pub mod shader;
+/// Runtime GLSL to SPIR-V compilation, enabled with the `glsl` feature.
+#[cfg(feature = "glsl")]
+pub mod glsl;
 
 // pub mod sprite;
 
 pub mod renderer;
 
 pub mod render_pass;
+/// Full-screen effects applied to the rendered scene before it is presented.
+pub mod postprocess;
+/// Built-in shader programs registered on the renderer's default render pass.
+pub mod library;
+/// Pooled buffers and cached descriptor sets for per-draw data.
+pub mod resources;
+/// Descriptor and push constant bindings checked against a shader's layout.
+pub mod bindings;
+/// Indexed meshes with user defined vertex types.
+pub mod mesh;
+/// Tessellates rectangles, circles, arcs, polygons and lines into triangle meshes.
+pub mod shapes;
+/// Lines, boxes, circles, arrows and labels drawn over the scene for debugging.
+pub mod debug_draw;
+/// Lists the system's Vulkan devices and picks the one the renderer uses.
+pub mod adapter;
+/// Validation layer setup, the debug messenger and debug names for Vulkan objects.
+pub mod debug;
+/// GPU timestamps around every frame and pass of a render pass.
+pub mod profiler;
+/// Texture uploads on a transfer queue and images loaded in the background.
+pub mod upload;
 
 // pub mod text;
 
@@ -33,83 +58,256 @@ use vulkano::descriptor_set::WriteDescriptorSet;
 use vulkano::device::{Device, Queue};
 use vulkano::image::view::ImageViewAbstract;
 use vulkano::sampler::Sampler;
-use vulkano::command_buffer::{AutoCommandBufferBuilder, SecondaryAutoCommandBuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, SecondaryAutoCommandBuffer};
+use vulkano::descriptor_set::PersistentDescriptorSet;
+use vulkano::descriptor_set::layout::DescriptorSetLayout;
+use vulkano::pipeline::graphics::viewport::{Scissor, Viewport};
+use crate::graphics::bindings::Bindings;
+use crate::graphics::mesh::IndexBuffer;
+use crate::graphics::resources::FrameResources;
 use crate::graphics::shader::ShaderHandle;
+use crate::error::GameError;
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 
-#[derive(Clone, Copy, PartialEq, Hash, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Hash, Eq)]
 pub enum BlendMode {
     Add,
     Subtract,
     Alpha,
     Invert,
-    // Multiply,
-    // Replace,
-    // Lighten,
-    // Darken,
+    Multiply,
+    /// Overwrites the destination, ignoring alpha.
+    Replace,
+    Lighten,
+    Darken,
+    /// Alpha blending for colors that have already been multiplied by their alpha.
+    Premultiplied,
+    /// Mixes source and destination by the program's blend constants.
+    Constant,
 }
 
+/// What a `Drawable` records a draw with, set up by the pass that draws it.
+pub struct DrawContext<'a> {
+    pub queue: Arc<Queue>,
+    pub resources: &'a FrameResources,
+    pub shader: &'a dyn ShaderHandle,
+    /// The current split screen view, or the whole framebuffer.
+    pub viewport: Viewport,
+    /// The current clip rect. Pipelines take the scissor as dynamic state, so every draw must
+    /// set it, which `command_buffer` does.
+    pub scissor: Scissor,
+    /// The matrix of the camera the pass draws with.
+    pub mvp: [[f32; 4]; 4],
+}
+
+impl DrawContext<'_> {
+    /// Starts a secondary command buffer for the shader's subpass with its pipeline bound and
+    /// the viewport and scissor set.
+    pub fn command_buffer(&self) -> Result<AutoCommandBufferBuilder<SecondaryAutoCommandBuffer>> {
+        let mut builder = AutoCommandBufferBuilder::secondary_graphics(
+            self.queue.device().clone(),
+            self.queue.family(),
+            CommandBufferUsage::MultipleSubmit,
+            self.shader.pipeline().subpass().clone(),
+        )?;
+
+        builder
+            .bind_pipeline_graphics(self.shader.pipeline())
+            .set_viewport(0, [self.viewport.clone()])
+            .set_scissor(0, [self.scissor]);
+        self.resources.record_pipeline_bind();
+
+        Ok(builder)
+    }
+
+    /// The set with `mvp` the built-in shaders read at set 0, shared by draws with the same
+    /// shader and camera this frame.
+    pub fn camera_set(&self) -> Result<Arc<PersistentDescriptorSet>> {
+        self.resources.uniform_set(self.set_layout(0)?, self.mvp)
+    }
+
+    /// The layout of descriptor set `set` of the shader, a `GameError::BindingError` when the
+    /// shader doesn't declare it.
+    pub fn set_layout(&self, set: usize) -> Result<Arc<DescriptorSetLayout>> {
+        self.shader.layout().get(set).cloned().ok_or_else(|| {
+            GameError::BindingError(format!("set {} is not declared by the shader", set)).into()
+        })
+    }
+}
+
+/// The addresses of the buffers, image views and samplers a drawable binds besides its
+/// instance data, unused entries are 0.
+pub type BatchKey = [usize; 3];
+
 pub trait Drawable {
     // fn draw(&self, context: &mut Renderer, info: DrawInfo);
-    fn draw(&self, queue: Arc<Queue>, shader_handle: &Box<dyn ShaderHandle>, info: DrawInfo) -> Result<SecondaryAutoCommandBuffer>;
+    fn draw(&self, context: &DrawContext, info: DrawInfo) -> Result<SecondaryAutoCommandBuffer>;
+
+    /// Identifies everything the drawable binds besides its instance data, usually its texture
+    /// and sampler. Consecutive `Pass::draw_with` calls with the same key, shader and blend mode
+    /// are merged into one `draw_instanced` call. `None` opts out
+    /// of batching.
+    fn batch_key(&self) -> Option<BatchKey> {
+        None
+    }
+
+    /// Draws the drawable once per instance, only called when `batch_key` returns `Some`.
+    fn draw_instanced(&self, _context: &DrawContext, _instances: &[InstanceData]) -> Result<SecondaryAutoCommandBuffer> {
+        Err(anyhow!("Drawable has a batch key but does not implement draw_instanced"))
+    }
 }
 
+/// Buffers, descriptors and push constants for a single draw with a custom shader, recorded
+/// with `Pass::draw_data`.
+///
+/// Descriptors are bound by set and binding through `Bindings` and checked against the shader's
+/// layout before drawing.
+///
+/// # Examples
+/// ```ignore
+/// let data = pass.pipeline_data()
+///     .vertex_buffer(vertices)
+///     .instance_buffer(vec![DrawInfo::default().into()])
+///     .uniform(0, 0, camera.as_mvp())
+///     .image_sampler(1, 0, image.inner().clone(), sampler.clone())
+///     .uniform(1, 1, Params { time })
+///     .push_constants(Tint { color: [1.0, 0.0, 0.0, 1.0] });
+/// pass.draw_data(shader, data)?;
+/// ```
 pub struct PipelineData {
     device: Arc<Device>,
     pub vertex_buffer: Arc<dyn BufferAccess>,
     pub vertex_count: u32,
     pub instance_buffer: Arc<dyn BufferAccess>,
     pub instance_count: u32,
-    pub descriptors: Vec<WriteDescriptorSet>,
+    pub index_buffer: Option<IndexBuffer>,
+    pub bindings: Bindings,
+    uploaded: usize,
 }
 
+impl PipelineData {
+    pub fn new(device: Arc<Device>) -> Self {
+        Self {
+            device: device.clone(),
+            vertex_buffer: CpuAccessibleBuffer::from_iter(
+                device.clone(),
+                vulkano::buffer::BufferUsage::vertex_buffer(),
+                true,
+                [Vertex::default()].iter().cloned(),
+            )
+            .unwrap(),
+            vertex_count: 0,
+            instance_buffer: CpuAccessibleBuffer::from_iter(
+                device.clone(),
+                vulkano::buffer::BufferUsage::vertex_buffer(),
+                true,
+                [InstanceData::default()].iter().cloned(),
+            )
+            .unwrap(),
+            instance_count: 0,
+            index_buffer: None,
+            bindings: Bindings::new(device),
+            uploaded: 0,
+        }
+    }
+
+    /// Bytes written into the buffers this data created, for `RenderStats`.
+    pub fn uploaded(&self) -> usize {
+        self.uploaded + self.bindings.uploaded()
+    }
 
+    /// The vertices, or indices when indexed, each instance draws.
+    pub fn element_count(&self) -> u32 {
+        self.index_buffer.as_ref().map_or(self.vertex_count, |i| i.len())
+    }
 
-impl PipelineData {
     fn flush(
         self,
     ) -> (
         Vec<Arc<dyn BufferAccess>>,
-        Vec<WriteDescriptorSet>,
+        Bindings,
+        Option<IndexBuffer>,
         u32,
         u32,
     ) {
         (
             vec![self.vertex_buffer, self.instance_buffer],
-            self.descriptors,
+            self.bindings,
+            self.index_buffer,
             self.vertex_count,
             self.instance_count,
         )
     }
 
-    pub fn buffer(mut self, binding: u32, buffer: Arc<dyn BufferAccess>) -> Self {
-        self.descriptors = Vec::new();
-
-        self.descriptors
-            .push(WriteDescriptorSet::buffer(binding, buffer));
-
+    fn with_bindings(mut self, f: impl FnOnce(Bindings) -> Bindings) -> Self {
+        self.bindings = f(self.bindings);
         self
     }
 
+    /// Binds a uniform buffer at `set = 1`.
+    pub fn buffer(self, binding: u32, buffer: Arc<dyn BufferAccess>) -> Self {
+        self.uniform_buffer(1, binding, buffer)
+    }
+
+    /// Binds an image and sampler at `set = 1`.
     pub fn sampled_image(
-        mut self,
+        self,
         binding: u32,
         image_view: Arc<dyn ImageViewAbstract>,
         sampler: Arc<Sampler>,
     ) -> Self {
-        self.descriptors = Vec::new();
+        self.image_sampler(1, binding, image_view, sampler)
+    }
+
+    pub fn uniform_buffer(self, set: u32, binding: u32, buffer: Arc<dyn BufferAccess>) -> Self {
+        self.with_bindings(|b| b.uniform_buffer(set, binding, buffer))
+    }
 
-        self.descriptors
-            .push(WriteDescriptorSet::image_view_sampler(
-                binding, image_view, sampler,
-            ));
+    /// Uploads `data` into a new uniform buffer and binds it.
+    pub fn uniform<T: Pod + Send + Sync>(self, set: u32, binding: u32, data: T) -> Self {
+        self.with_bindings(|b| b.uniform(set, binding, data))
+    }
+
+    pub fn storage_buffer(self, set: u32, binding: u32, buffer: Arc<dyn BufferAccess>) -> Self {
+        self.with_bindings(|b| b.storage_buffer(set, binding, buffer))
+    }
 
+    pub fn image_sampler(
+        self,
+        set: u32,
+        binding: u32,
+        image_view: Arc<dyn ImageViewAbstract>,
+        sampler: Arc<Sampler>,
+    ) -> Self {
+        self.with_bindings(|b| b.image_sampler(set, binding, image_view, sampler))
+    }
+
+    pub fn storage_image(self, set: u32, binding: u32, image_view: Arc<dyn ImageViewAbstract>) -> Self {
+        self.with_bindings(|b| b.storage_image(set, binding, image_view))
+    }
+
+    /// Sets the push constants, starting at offset 0. The size of `T` must be a multiple of 4.
+    pub fn push_constants<T: Pod>(self, data: T) -> Self {
+        self.with_bindings(|b| b.push_constants(data))
+    }
+
+    /// Draws the vertices through `indices` instead of in order.
+    pub fn indices_u16(mut self, indices: Vec<u16>) -> Self {
+        self.uploaded += indices.len() * std::mem::size_of::<u16>();
+        self.index_buffer = Some(IndexBuffer::u16(self.device.clone(), indices).unwrap());
+        self
+    }
+
+    pub fn indices_u32(mut self, indices: Vec<u32>) -> Self {
+        self.uploaded += indices.len() * std::mem::size_of::<u32>();
+        self.index_buffer = Some(IndexBuffer::u32(self.device.clone(), indices).unwrap());
         self
     }
 
     pub fn vertex_buffer(mut self, vertex_buffer: Vec<Vertex>) -> Self {
         self.vertex_count = vertex_buffer.len() as u32;
+        self.uploaded += vertex_buffer.len() * std::mem::size_of::<Vertex>();
         self.vertex_buffer = CpuAccessibleBuffer::from_iter(
             self.device.clone(),
             BufferUsage::vertex_buffer(),
@@ -121,8 +319,16 @@ impl PipelineData {
         self
     }
 
+    /// Uses an existing buffer, such as the output of a compute shader, as the vertex buffer.
+    pub fn vertex_buffer_from(mut self, buffer: Arc<dyn BufferAccess>, count: u32) -> Self {
+        self.vertex_buffer = buffer;
+        self.vertex_count = count;
+        self
+    }
+
     pub fn instance_buffer(mut self, instance_buffer: Vec<InstanceData>) -> Self {
         self.instance_count = instance_buffer.len() as u32;
+        self.uploaded += instance_buffer.len() * std::mem::size_of::<InstanceData>();
         self.instance_buffer = CpuAccessibleBuffer::from_iter(
             self.device.clone(),
             BufferUsage::vertex_buffer(),
@@ -134,27 +340,11 @@ impl PipelineData {
         self
     }
 
-    fn new(device: Arc<vulkano::device::Device>) -> Self {
-        Self {
-            device: device.clone(),
-            vertex_buffer: CpuAccessibleBuffer::from_iter(
-                device.clone(),
-                vulkano::buffer::BufferUsage::vertex_buffer(),
-                true,
-                [Vertex::default()].iter().cloned(),
-            )
-            .unwrap(),
-            vertex_count: 0,
-            instance_buffer: CpuAccessibleBuffer::from_iter(
-                device.clone(),
-                vulkano::buffer::BufferUsage::vertex_buffer(),
-                true,
-                [InstanceData::default()].iter().cloned(),
-            )
-            .unwrap(),
-            instance_count: 0,
-            descriptors: Vec::new(),
-        }
+    /// Uses an existing buffer, such as the output of a compute shader, as the instance buffer.
+    pub fn instance_buffer_from(mut self, buffer: Arc<dyn BufferAccess>, count: u32) -> Self {
+        self.instance_buffer = buffer;
+        self.instance_count = count;
+        self
     }
 }
 
@@ -174,9 +364,10 @@ pub struct InstanceData {
     src: [f32; 4],
     color: [f32; 4],
     transform: [[f32; 4]; 4],
+    layer: u32,
 }
 
-vulkano::impl_vertex!(InstanceData, src, color, transform);
+vulkano::impl_vertex!(InstanceData, src, color, transform, layer);
 
 impl From<DrawInfo> for InstanceData {
     fn from(info: DrawInfo) -> InstanceData {
@@ -184,6 +375,7 @@ impl From<DrawInfo> for InstanceData {
             src: info.tex_rect.as_vec(),
             color: info.color.into(),
             transform: info.transform.as_mat4().into(),
+            layer: info.layer,
         }
     }
 }
@@ -194,6 +386,7 @@ impl From<&DrawInfo> for InstanceData {
             src: info.tex_rect.as_vec(),
             color: info.color.into(),
             transform: info.transform.as_mat4().into(),
+            layer: info.layer,
         }
     }
 }
@@ -234,6 +427,8 @@ pub struct DrawInfo {
     pub tex_rect: Rect,
     pub color: Color,
     pub transform: Transform,
+    /// The layer of a `TextureArray` to sample, ignored by single images.
+    pub layer: u32,
 }
 
 impl Default for DrawInfo {
@@ -242,6 +437,7 @@ impl Default for DrawInfo {
             tex_rect: Rect::default(),
             color: Color::white(),
             transform: Transform::identity(),
+            layer: 0,
         }
     }
 }
@@ -252,6 +448,7 @@ impl DrawInfo {
             tex_rect: Rect::default(),
             color: Color::white(),
             transform: Transform::identity(),
+            layer: 0,
         }
     }
 
@@ -259,6 +456,7 @@ impl DrawInfo {
         self.tex_rect = Rect::default();
         self.color = Color::white();
         self.transform = Transform::identity();
+        self.layer = 0;
     }
 
     pub fn with_rect(rect: Rect) -> Self {
@@ -266,6 +464,7 @@ impl DrawInfo {
             tex_rect: rect,
             color: Color::white(),
             transform: Transform::identity(),
+            layer: 0,
         }
     }
 
@@ -274,6 +473,7 @@ impl DrawInfo {
             tex_rect: Rect::default(),
             color: Color::white(),
             transform: transform,
+            layer: 0,
         }
     }
 
@@ -282,6 +482,7 @@ impl DrawInfo {
             tex_rect: Rect::default(),
             color: color,
             transform: Transform::identity(),
+            layer: 0,
         }
     }
 
@@ -289,6 +490,10 @@ impl DrawInfo {
         self.color = color;
     }
 
+    pub fn layer(&mut self, layer: u32) {
+        self.layer = layer;
+    }
+
     pub fn tex_offset(&mut self, offset: (f32, f32)) {
         self.tex_rect.x = offset.0;
         self.tex_rect.y = offset.1;
@@ -0,0 +1,342 @@
+use crate::graphics::camera::{Camera, OrthographicCamera};
+use crate::graphics::debug;
+use crate::graphics::library::vertex_definition;
+use crate::graphics::render_pass::{frame, RenderPass};
+use crate::graphics::resources::FrameResources;
+use crate::graphics::shader::{Shader, ShaderId, VertexTopology};
+use crate::graphics::*;
+
+use bytemuck::{Pod, Zeroable};
+use vulkano::descriptor_set::PersistentDescriptorSet;
+use vulkano::format::Format;
+use vulkano::image::{view::ImageView, AttachmentImage, ImageAccess, ImageUsage};
+use vulkano::pipeline::{Pipeline, PipelineBindPoint};
+use vulkano::shader::ShaderModule;
+use vulkano::sync::GpuFuture;
+
+use anyhow::{anyhow, Result};
+
+pub type EffectId = usize;
+
+/// Vignette effect, darkens the edges of the screen. Parameterized with [`VignetteParams`].
+pub mod vignette {
+    vulkano_shaders::shader! { ty: "fragment", path: "src/graphics/shaders/vignette.frag", }
+}
+
+/// CRT style scanlines. Parameterized with [`ScanlineParams`].
+pub mod scanlines {
+    vulkano_shaders::shader! { ty: "fragment", path: "src/graphics/shaders/scanlines.frag", }
+}
+
+/// Reduces every color channel to a fixed number of levels. Parameterized with [`QuantizeParams`].
+pub mod quantize {
+    vulkano_shaders::shader! { ty: "fragment", path: "src/graphics/shaders/quantize.frag", }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Zeroable, Pod)]
+pub struct VignetteParams {
+    pub intensity: f32,
+    pub radius: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Zeroable, Pod)]
+pub struct ScanlineParams {
+    pub intensity: f32,
+    pub count: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Zeroable, Pod)]
+pub struct QuantizeParams {
+    pub levels: f32,
+}
+
+struct Effect {
+    shader: ShaderId,
+    // Uploaded into a uniform buffer of the frame by every `apply`.
+    params: Option<Vec<u8>>,
+    enabled: bool,
+}
+
+/// A chain of full-screen effects applied to the scene before it is presented.
+///
+/// The scene is drawn into an intermediate target returned by `scene_target` instead of the
+/// swapchain image, then `apply` runs every enabled effect in registration order, ping-ponging
+/// between two targets, with the last effect writing into the final image.
+///
+/// Effects are fragment shaders that follow the layout of the built-in texture shader: the
+/// previous result is bound as a `sampler2D` at `set = 1, binding = 0` and the optional
+/// parameters as a uniform block at `set = 1, binding = 1`.
+///
+/// # Examples
+/// ```ignore
+/// let mut post = PostProcess::new(
+///     renderer.queue.clone(),
+///     renderer.resources.clone(),
+///     renderer.samplers[0].clone(),
+///     renderer.output_format(),
+/// )?;
+/// let fs = postprocess::vignette::load(renderer.device.clone())?;
+/// let vignette = post.register_effect(fs)?;
+/// post.set_params(vignette, VignetteParams { intensity: 0.8, radius: 0.3 })?;
+///
+/// // In draw:
+/// let scene = post.scene_target(renderer.final_image().image().dimensions().width_height())?;
+/// // ... draw the scene into `scene` with any render pass, then
+/// let after_future = post.apply(after_scene_future, renderer.final_image())?;
+/// ```
+pub struct PostProcess {
+    queue: Arc<Queue>,
+    resources: Arc<FrameResources>,
+    sampler: Arc<Sampler>,
+    format: Format,
+    render_pass: RenderPass,
+    vertex_module: Arc<ShaderModule>,
+    passthrough: ShaderId,
+    effects: Vec<Effect>,
+    targets: Vec<Arc<ImageView<AttachmentImage>>>,
+    dimensions: [u32; 2],
+    camera: Arc<dyn Camera>,
+}
+
+impl PostProcess {
+    pub fn new(queue: Arc<Queue>, resources: Arc<FrameResources>, sampler: Arc<Sampler>, format: Format) -> Result<Self> {
+        let device = queue.device().clone();
+
+        let render_pass = vulkano::single_pass_renderpass!(device.clone(),
+            attachments: {
+                color: {
+                    load: Clear,
+                    store: Store,
+                    format: format,
+                    samples: 1,
+                }
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {}
+            }
+        )?;
+
+        let vertex_module = vs::load(device.clone())?;
+        let fragment_module = fs::load(device.clone())?;
+
+        let mut render_pass = RenderPass::new(queue.clone(), resources.clone(), render_pass)?;
+        render_pass.set_name("post-process");
+        render_pass.set_scene_camera(false);
+        let passthrough = render_pass.register_shader(
+            Arc::new(Shader::new(
+                vertex_module.clone(),
+                fragment_module,
+                VertexTopology::TriangleStrip,
+            )),
+            vertex_definition(),
+        )?;
+
+        Ok(Self {
+            queue,
+            resources,
+            sampler,
+            format,
+            render_pass,
+            vertex_module,
+            passthrough,
+            effects: Vec::new(),
+            targets: Vec::new(),
+            dimensions: [0, 0],
+            camera: Arc::new(OrthographicCamera::default()),
+        })
+    }
+
+    /// Registers a full-screen fragment shader at the end of the chain.
+    pub fn register_effect(&mut self, fragment: Arc<ShaderModule>) -> Result<EffectId> {
+        let shader = self.render_pass.register_shader(
+            Arc::new(Shader::new(
+                self.vertex_module.clone(),
+                fragment,
+                VertexTopology::TriangleStrip,
+            )),
+            vertex_definition(),
+        )?;
+
+        self.effects.push(Effect {
+            shader,
+            params: None,
+            enabled: true,
+        });
+
+        Ok(self.effects.len() - 1)
+    }
+
+    /// Sets the uniform parameters bound at `set = 1, binding = 1` for an effect. They are
+    /// copied into a uniform buffer from the frame's pool whenever the effect is applied.
+    pub fn set_params<T: Pod + Send + Sync>(&mut self, id: EffectId, params: T) -> Result<()> {
+        self.effect_mut(id)?.params = Some(bytemuck::bytes_of(&params).to_vec());
+
+        Ok(())
+    }
+
+    pub fn set_enabled(&mut self, id: EffectId, enabled: bool) -> Result<()> {
+        self.effect_mut(id)?.enabled = enabled;
+
+        Ok(())
+    }
+
+    pub fn enabled(&self, id: EffectId) -> bool {
+        self.effects.get(id).is_some_and(|e| e.enabled)
+    }
+
+    /// Returns the image the scene should be drawn into this frame, recreating the
+    /// intermediate targets when the dimensions changed.
+    pub fn scene_target(&mut self, dimensions: [u32; 2]) -> Result<Arc<dyn ImageViewAbstract>> {
+        if self.targets.is_empty() || self.dimensions != dimensions {
+            let usage = ImageUsage {
+                sampled: true,
+                color_attachment: true,
+                ..ImageUsage::none()
+            };
+
+            let mut targets = Vec::with_capacity(2);
+            for _ in 0..2 {
+                let image = AttachmentImage::with_usage(
+                    self.queue.device().clone(),
+                    dimensions,
+                    self.format,
+                    usage,
+                )?;
+                debug::set_name(self.queue.device(), image.inner().image, &format!("post-process target {}", targets.len()));
+                targets.push(ImageView::new_default(image)?);
+            }
+
+            self.targets = targets;
+            self.dimensions = dimensions;
+        }
+
+        Ok(self.targets[0].clone())
+    }
+
+    /// Runs the enabled effects over the scene target and writes the result into `final_image`.
+    ///
+    /// When no effect is enabled the scene is copied through unchanged.
+    pub fn apply(
+        &mut self,
+        before_future: Box<dyn GpuFuture>,
+        final_image: Arc<dyn ImageViewAbstract>,
+    ) -> Result<Box<dyn GpuFuture>> {
+        if self.targets.is_empty() {
+            return Err(anyhow!("PostProcess::scene_target was not called before apply"));
+        }
+
+        let mut chain = Vec::new();
+        for effect in self.effects.iter().filter(|e| e.enabled) {
+            let params = match &effect.params {
+                Some(bytes) => Some(self.resources.uniform_bytes(bytes)? as Arc<dyn BufferAccess>),
+                None => None,
+            };
+            chain.push((effect.shader, params));
+        }
+
+        if chain.is_empty() {
+            chain.push((self.passthrough, None));
+        }
+
+        let mut future = before_future;
+        let last = chain.len() - 1;
+
+        for (i, (shader, params)) in chain.into_iter().enumerate() {
+            let source: Arc<dyn ImageViewAbstract> = self.targets[i % 2].clone();
+            let target: Arc<dyn ImageViewAbstract> = if i == last {
+                final_image.clone()
+            } else {
+                self.targets[(i + 1) % 2].clone()
+            };
+
+            let quad = Arc::new(EffectQuad {
+                source,
+                sampler: self.sampler.clone(),
+                params,
+            });
+
+            let mut frame = self.render_pass.frame(
+                Color::transparent().into(),
+                future,
+                target,
+                self.camera.clone(),
+            )?;
+
+            let mut after_future = None;
+            while let Some(pass) = frame.next_pass()? {
+                after_future = match pass {
+                    frame::PassState::DrawPass(mut pass) => {
+                        pass.draw_with(quad.clone(), shader, fullscreen_info())?;
+                        None
+                    }
+                    frame::PassState::Finished(af) => Some(af),
+                }
+            }
+
+            future = after_future.unwrap();
+        }
+
+        Ok(future)
+    }
+
+    fn effect_mut(&mut self, id: EffectId) -> Result<&mut Effect> {
+        self.effects
+            .get_mut(id)
+            .ok_or_else(|| anyhow!("No post-processing effect with id {}", id))
+    }
+}
+
+// Maps the unit quad onto the whole of clip space.
+fn fullscreen_info() -> DrawInfo {
+    let mut info = DrawInfo::default();
+    info.nonuniform_scale(2.0, 2.0, 1.0);
+    info.dest(-1.0, -1.0, 0.0);
+    info
+}
+
+pub(crate) struct EffectQuad {
+    pub(crate) source: Arc<dyn ImageViewAbstract>,
+    pub(crate) sampler: Arc<Sampler>,
+    pub(crate) params: Option<Arc<dyn BufferAccess>>,
+}
+
+impl Drawable for EffectQuad {
+    fn draw(&self, context: &DrawContext, info: DrawInfo) -> Result<SecondaryAutoCommandBuffer> {
+        let (resources, shader_handle) = (context.resources, context.shader);
+        let mut builder = context.command_buffer()?;
+
+        let vertex_buffer = resources.quad();
+        let instance_buffer = resources.instances([InstanceData::from(info)])?;
+
+        let cam_set = context.camera_set()?;
+
+        let mut writes = vec![WriteDescriptorSet::image_view_sampler(
+            0,
+            self.source.clone(),
+            self.sampler.clone(),
+        )];
+        if let Some(params) = &self.params {
+            writes.push(WriteDescriptorSet::buffer(1, params.clone()));
+        }
+
+        let set = PersistentDescriptorSet::new(shader_handle.layout()[1].clone(), writes)?;
+        resources.record_descriptor_sets(1);
+
+        builder
+            .bind_vertex_buffers(0, (vertex_buffer, instance_buffer))
+            .bind_descriptor_sets(
+                PipelineBindPoint::Graphics,
+                shader_handle.pipeline().layout().clone(),
+                0,
+                (cam_set, set),
+            )
+            .draw(QUAD_VERTICES.len() as u32, 1, 0, 0)?;
+        resources.record_vertices(QUAD_VERTICES.len() as u32, 1);
+
+        Ok(builder.build()?)
+    }
+}
@@ -0,0 +1,188 @@
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use vulkano::device::Queue;
+use vulkano::query::{QueryPool, QueryPoolCreateInfo, QueryResultFlags, QueryType};
+
+use anyhow::Result;
+
+/// Timestamps available to the frames recorded between two `begin_frame` calls, two for each
+/// frame and two for each of its passes.
+pub const QUERIES_PER_FRAME: u32 = 256;
+
+/// GPU time spent on a render pass frame, or on one of its passes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PassTiming {
+    /// The name set with `RenderPass::set_name`.
+    pub render_pass: String,
+    /// The pass index, or `None` for the whole frame.
+    pub pass: Option<u8>,
+    pub milliseconds: f32,
+}
+
+/// The timestamp queries reserved for one `Frame`.
+pub(crate) struct FrameQueries {
+    pub(crate) pool: Arc<QueryPool>,
+    first: u32,
+    passes: u8,
+}
+
+impl FrameQueries {
+    pub(crate) fn range(&self) -> Range<u32> {
+        self.first..self.first + 2 + 2 * self.passes as u32
+    }
+
+    pub(crate) fn frame_start(&self) -> u32 {
+        self.first
+    }
+
+    pub(crate) fn frame_end(&self) -> u32 {
+        self.first + 1
+    }
+
+    pub(crate) fn pass_start(&self, pass: u8) -> u32 {
+        self.first + 2 + 2 * pass as u32
+    }
+
+    pub(crate) fn pass_end(&self, pass: u8) -> u32 {
+        self.pass_start(pass) + 1
+    }
+}
+
+// A frame's name and the first of its queries.
+struct Span {
+    render_pass: String,
+    first: u32,
+    passes: u8,
+}
+
+struct ProfilerSlot {
+    pool: Arc<QueryPool>,
+    spans: Vec<Span>,
+    next: u32,
+}
+
+/// Times frames and passes on the GPU with timestamp queries.
+///
+/// Each frame in flight has its own query pool. Results are read when `begin_frame` comes back
+/// around to a pool, after the renderer has waited on the fence of the frame that used it, and
+/// are dropped instead of waited for when they are still not available.
+pub(crate) struct GpuProfiler {
+    // Nanoseconds per timestamp tick.
+    period: f32,
+    valid_mask: u64,
+    slots: Vec<Mutex<ProfilerSlot>>,
+    current: AtomicUsize,
+    timings: Mutex<Vec<PassTiming>>,
+}
+
+impl GpuProfiler {
+    /// Returns `None` when the queue cannot write timestamps.
+    pub(crate) fn new(queue: &Queue, frames: usize) -> Result<Option<Self>> {
+        let valid_bits = match queue.family().timestamp_valid_bits() {
+            Some(bits) => bits,
+            None => return Ok(None),
+        };
+
+        let mut slots = Vec::with_capacity(frames);
+        for _ in 0..frames {
+            let pool = QueryPool::new(
+                queue.device().clone(),
+                QueryPoolCreateInfo {
+                    query_count: QUERIES_PER_FRAME,
+                    ..QueryPoolCreateInfo::query_type(QueryType::Timestamp)
+                },
+            )?;
+
+            slots.push(Mutex::new(ProfilerSlot {
+                pool,
+                spans: Vec::new(),
+                next: 0,
+            }));
+        }
+
+        Ok(Some(Self {
+            period: queue.device().physical_device().properties().timestamp_period,
+            valid_mask: if valid_bits >= 64 { u64::MAX } else { (1 << valid_bits) - 1 },
+            slots,
+            current: AtomicUsize::new(0),
+            timings: Mutex::new(Vec::new()),
+        }))
+    }
+
+    /// Reads the results of the slot's last use and frees its queries.
+    pub(crate) fn begin_frame(&self, slot: usize) {
+        self.current.store(slot, Ordering::Relaxed);
+        let mut slot = self.slots[slot].lock().unwrap();
+
+        if slot.next > 0 {
+            let mut results = vec![0u64; slot.next as usize];
+            let available = slot
+                .pool
+                .queries_range(0..slot.next)
+                .unwrap()
+                .get_results(&mut results, QueryResultFlags::default())
+                .unwrap_or(false);
+
+            if available {
+                let ms = |start: u32, end: u32| {
+                    let ticks = results[end as usize].wrapping_sub(results[start as usize]) & self.valid_mask;
+                    ticks as f32 * self.period / 1_000_000.0
+                };
+
+                let mut timings = Vec::new();
+                for span in &slot.spans {
+                    timings.push(PassTiming {
+                        render_pass: span.render_pass.clone(),
+                        pass: None,
+                        milliseconds: ms(span.first, span.first + 1),
+                    });
+
+                    for pass in 0..span.passes as u32 {
+                        let start = span.first + 2 + 2 * pass;
+                        timings.push(PassTiming {
+                            render_pass: span.render_pass.clone(),
+                            pass: Some(pass as u8),
+                            milliseconds: ms(start, start + 1),
+                        });
+                    }
+                }
+
+                *self.timings.lock().unwrap() = timings;
+            }
+        }
+
+        slot.spans.clear();
+        slot.next = 0;
+    }
+
+    /// Reserves the queries for a frame of a render pass with `passes` passes, `None` when the
+    /// frame's queries have run out.
+    pub(crate) fn reserve(&self, render_pass: &str, passes: u8) -> Option<FrameQueries> {
+        let mut slot = self.slots[self.current.load(Ordering::Relaxed)].lock().unwrap();
+
+        let count = 2 + 2 * passes as u32;
+        if slot.next + count > QUERIES_PER_FRAME {
+            return None;
+        }
+
+        let first = slot.next;
+        slot.next += count;
+        slot.spans.push(Span {
+            render_pass: render_pass.to_string(),
+            first,
+            passes,
+        });
+
+        Some(FrameQueries {
+            pool: slot.pool.clone(),
+            first,
+            passes,
+        })
+    }
+
+    /// The timings of the most recent frame whose results were read.
+    pub(crate) fn timings(&self) -> Vec<PassTiming> {
+        self.timings.lock().unwrap().clone()
+    }
+}
@@ -1,35 +1,134 @@
 use vulkano::{
     command_buffer::{
-        AutoCommandBufferBuilder, PrimaryAutoCommandBuffer,
+        AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer, PrimaryCommandBuffer,
+        SecondaryAutoCommandBuffer,
     },
+    pipeline::graphics::viewport::{Scissor, Viewport},
+    render_pass::Subpass,
+    command_buffer::SubpassContents,
+    sync::PipelineStage,
     // render_pass::{Framebuffer},
     sync::{GpuFuture},
     device::Queue,
 };
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use std::sync::Arc;
 
+use crate::graphics::camera::Camera;
+use crate::graphics::debug;
+use crate::graphics::profiler::FrameQueries;
+use crate::graphics::resources::FrameResources;
 use crate::graphics::shader::*;
-use crate::graphics::{Drawable, DrawInfo};
+use crate::graphics::{BatchKey, BlendMode, DrawContext, Drawable, DrawInfo, InstanceData, PipelineData, Rect};
 // use crate::graphics::camera::Camera2D;
 
 pub struct Frame<'p> {
-    pub(crate) pipelines: &'p Vec<Box<dyn ShaderHandle>>,
+    pub(crate) pipelines: &'p mut Vec<Box<dyn ShaderHandle>>,
     pub(crate) num_pass: u8,
+    pub(crate) dimensions: [u32; 2],
     pub(crate) cur_pass: u8,
     pub(crate) queue: Arc<Queue>,
+    pub(crate) resources: Arc<FrameResources>,
     pub(crate) before_main_cb_future: Option<Box<dyn GpuFuture>>,
     // pub(crate) framebuffer: Arc<Framebuffer>,
     pub(crate) command_buffer: Option<AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>>,
-    // pub(crate) camera: Camera2D,
+    pub(crate) batch: Option<Batch>,
+    pub(crate) camera: Arc<dyn Camera>,
+    pub(crate) clips: Vec<Scissor>,
+    pub(crate) view: Option<View>,
+    pub(crate) render_pass: Arc<vulkano::render_pass::RenderPass>,
+    pub(crate) timestamps: Option<FrameQueries>,
+}
+
+/// A part of the framebuffer drawn with its own camera, for split screen.
+///
+/// `rect` is in fractions of the framebuffer, from its top left corner, so views keep their
+/// layout when the window is resized.
+#[derive(Clone)]
+pub struct View {
+    pub rect: Rect,
+    pub camera: Arc<dyn Camera>,
+}
+
+impl View {
+    pub fn new(rect: Rect, camera: Arc<dyn Camera>) -> Self {
+        Self { rect, camera }
+    }
+
+    /// One view per camera laid out for local multiplayer: side by side for two players, one
+    /// on top and two below for three, and quadrants for four. More than four cameras are
+    /// placed in a grid.
+    pub fn split_screen(cameras: Vec<Arc<dyn Camera>>) -> Vec<View> {
+        let rect = |x, y, w, h| Rect { x, y, w, h };
+
+        let rects = match cameras.len() {
+            0 => Vec::new(),
+            1 => vec![rect(0.0, 0.0, 1.0, 1.0)],
+            2 => vec![rect(0.0, 0.0, 0.5, 1.0), rect(0.5, 0.0, 0.5, 1.0)],
+            3 => vec![
+                rect(0.0, 0.0, 1.0, 0.5),
+                rect(0.0, 0.5, 0.5, 0.5),
+                rect(0.5, 0.5, 0.5, 0.5),
+            ],
+            n => {
+                let columns = (n as f32).sqrt().ceil() as usize;
+                let rows = n.div_ceil(columns);
+                let (w, h) = (1.0 / columns as f32, 1.0 / rows as f32);
+
+                (0..n)
+                    .map(|i| rect((i % columns) as f32 * w, (i / columns) as f32 * h, w, h))
+                    .collect()
+            }
+        };
+
+        rects.into_iter().zip(cameras).map(|(rect, camera)| View { rect, camera }).collect()
+    }
+}
+
+/// The coordinates a clip rect is given in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ClipSpace {
+    /// Pixels of the framebuffer, from its top left corner.
+    Screen,
+    /// The coordinates draws are given in, transformed by the frame's camera.
+    World,
+}
+
+// Draws queued by `Pass::draw_with` that are waiting to be recorded as one instanced draw.
+pub(crate) struct Batch {
+    drawable: Arc<dyn Drawable>,
+    shader: ShaderId,
+    blend: BlendMode,
+    key: BatchKey,
+    instances: Vec<InstanceData>,
 }
 
 impl<'p> Frame<'p> {
     pub fn next_pass<'f>(&'f mut self) -> Result<Option<PassState<'f, 'p>>> {
+        self.flush_batch()?;
+
+        if self.cur_pass > 0 && self.cur_pass <= self.num_pass {
+            if let Some(query) = self.timestamps.as_ref().map(|t| t.pass_end(self.cur_pass - 1)) {
+                self.write_pass_timestamp(query, PipelineStage::BottomOfPipe)?;
+            }
+        }
+
         Ok(
             if self.cur_pass < self.num_pass {
+                if self.cur_pass > 0 {
+                    self.command_buffer
+                        .as_mut()
+                        .unwrap()
+                        .next_subpass(SubpassContents::SecondaryCommandBuffers)?;
+                }
+
                 self.cur_pass += 1;
+
+                if let Some(query) = self.timestamps.as_ref().map(|t| t.pass_start(self.cur_pass - 1)) {
+                    self.write_pass_timestamp(query, PipelineStage::TopOfPipe)?;
+                }
+
                 Some(PassState::DrawPass(Pass {
                     frame: self,
                 }))
@@ -40,7 +139,18 @@ impl<'p> Frame<'p> {
                 .unwrap()
                 .end_render_pass()?;
 
+                if let Some(queries) = &self.timestamps {
+                    unsafe {
+                        self.command_buffer.as_mut().unwrap().write_timestamp(
+                            queries.pool.clone(),
+                            queries.frame_end(),
+                            PipelineStage::BottomOfPipe,
+                        )?;
+                    }
+                }
+
                 let command_buffer = self.command_buffer.take().unwrap().build()?;
+                debug::set_name(self.queue.device(), command_buffer.inner(), "frame");
 
                 let after_main_cb = self
                     .before_main_cb_future
@@ -55,6 +165,157 @@ impl<'p> Frame<'p> {
         )
        
     }
+
+    // Inside a subpass the primary command buffer can only execute secondary ones, so the
+    // timestamp is written from one.
+    fn write_pass_timestamp(&mut self, query: u32, stage: PipelineStage) -> Result<()> {
+        let queries = self.timestamps.as_ref().unwrap();
+        let subpass = Subpass::from(self.render_pass.clone(), self.cur_pass as u32 - 1).unwrap();
+
+        let mut builder = AutoCommandBufferBuilder::secondary_graphics(
+            self.queue.device().clone(),
+            self.queue.family(),
+            CommandBufferUsage::OneTimeSubmit,
+            subpass,
+        )?;
+
+        unsafe {
+            builder.write_timestamp(queries.pool.clone(), query, stage)?;
+        }
+
+        self.execute(builder.build()?)
+    }
+
+    fn execute(&mut self, commands: SecondaryAutoCommandBuffer) -> Result<()> {
+        self.command_buffer.as_mut().unwrap().execute_commands(commands)?;
+        self.resources.record_command_buffer();
+
+        Ok(())
+    }
+
+    // Executes the commands of a single draw, counting it whatever recorded it.
+    fn execute_draw(&mut self, commands: SecondaryAutoCommandBuffer) -> Result<()> {
+        self.execute(commands)?;
+        self.resources.record_draw_call();
+
+        Ok(())
+    }
+
+    // The pixel rect of the current view, or of the whole framebuffer outside of views.
+    fn view_rect(&self) -> [f32; 4] {
+        let [width, height] = [self.dimensions[0] as f32, self.dimensions[1] as f32];
+
+        match &self.view {
+            Some(view) => [
+                view.rect.x * width,
+                view.rect.y * height,
+                view.rect.w * width,
+                view.rect.h * height,
+            ],
+            None => [0.0, 0.0, width, height],
+        }
+    }
+
+    fn camera(&self) -> &Arc<dyn Camera> {
+        self.view.as_ref().map_or(&self.camera, |view| &view.camera)
+    }
+
+    fn viewport(&self) -> Viewport {
+        let [x, y, w, h] = self.view_rect();
+
+        Viewport {
+            origin: [x, y],
+            dimensions: [w, h],
+            depth_range: 0.0..1.0,
+        }
+    }
+
+    // The current view clipped to the top of the clip stack, clips pushed outside of a view
+    // never let draws spill out of it.
+    fn scissor(&self) -> Scissor {
+        let [x, y, w, h] = self.view_rect();
+        let mut start = [x.round() as u32, y.round() as u32];
+        let mut end = [
+            ((x + w).round() as u32).min(self.dimensions[0]),
+            ((y + h).round() as u32).min(self.dimensions[1]),
+        ];
+
+        if let Some(clip) = self.clips.last() {
+            for axis in 0..2 {
+                start[axis] = start[axis].max(clip.origin[axis]);
+                end[axis] = end[axis].min(clip.origin[axis] + clip.dimensions[axis]);
+            }
+        }
+
+        Scissor {
+            origin: start,
+            dimensions: [end[0].saturating_sub(start[0]), end[1].saturating_sub(start[1])],
+        }
+    }
+
+    // The viewport, clip and camera drawables record with in the current state of the frame.
+    fn draw_context(&self, id: ShaderId) -> Result<DrawContext<'_>> {
+        let shader = self
+            .pipelines
+            .get(id)
+            .ok_or_else(|| anyhow!("No shader registered with id {}", id))?;
+
+        Ok(DrawContext {
+            queue: self.queue.clone(),
+            resources: &self.resources,
+            shader: shader.as_ref(),
+            viewport: self.viewport(),
+            scissor: self.scissor(),
+            mvp: self.camera().as_mvp(),
+        })
+    }
+
+    // The pixel rect covered by `rect` in world coordinates after the camera transform.
+    fn world_to_screen(&self, rect: Rect) -> [f32; 4] {
+        let m = self.camera().as_mvp();
+        let [view_x, view_y, view_w, view_h] = self.view_rect();
+        let corners = [
+            [rect.x, rect.y],
+            [rect.x + rect.w, rect.y],
+            [rect.x, rect.y + rect.h],
+            [rect.x + rect.w, rect.y + rect.h],
+        ];
+
+        let (mut min, mut max) = ([f32::MAX; 2], [f32::MIN; 2]);
+        for [x, y] in corners {
+            // The matrix is column major, as the shaders read it.
+            let w = m[0][3] * x + m[1][3] * y + m[3][3];
+            for axis in 0..2 {
+                let ndc = (m[0][axis] * x + m[1][axis] * y + m[3][axis]) / w;
+                let pixel = [view_x, view_y][axis] + (ndc + 1.0) / 2.0 * [view_w, view_h][axis];
+                min[axis] = min[axis].min(pixel);
+                max[axis] = max[axis].max(pixel);
+            }
+        }
+
+        [min[0], min[1], max[0], max[1]]
+    }
+
+    fn flush_batch(&mut self) -> Result<()> {
+        let batch = match self.batch.take() {
+            Some(batch) => batch,
+            None => return Ok(()),
+        };
+
+        let previous = self.pipelines[batch.shader].blend_mode();
+        self.pipelines[batch.shader].set_blend_mode(batch.blend)?;
+
+        let commands = self
+            .draw_context(batch.shader)
+            .and_then(|context| batch.drawable.draw_instanced(&context, &batch.instances));
+
+        self.pipelines[batch.shader].set_blend_mode(previous)?;
+
+        self.execute_draw(commands?)?;
+        self.resources.record_draws_saved(batch.instances.len() - 1);
+
+        Ok(())
+    }
 }
 
 pub enum PassState<'f, 'p: 'f> {
@@ -67,17 +328,191 @@ pub struct Pass<'f, 'p> {
 }
 
 impl<'f, 'p> Pass<'f, 'p> {
+    /// Draws with the shader's current blend mode.
+    ///
+    /// Consecutive draws of drawables with the same `batch_key`, shader and blend mode are
+    /// queued and merged into one instanced draw. The queue is recorded when a draw with
+    /// different state comes in, when the clip rect or view changes, when `draw_data` or
+    /// `flush` is called, or when the pass ends. Drawables without a `batch_key` are drawn
+    /// right away.
     pub fn draw_with(&mut self, d: Arc<dyn Drawable>, id: ShaderId, draw_info: DrawInfo) -> Result<()> {
-        let shader_handle = self.frame.pipelines.get(id).unwrap();
+        let blend = self.frame
+            .pipelines
+            .get(id)
+            .ok_or_else(|| anyhow!("No shader registered with id {}", id))?
+            .blend_mode();
+
+        self.draw_with_blend(d, id, blend, draw_info)
+    }
+
+    /// Draws with the shader using `blend` for this draw only, batched like `draw_with`. The
+    /// pipeline for a blend mode is built the first time it is used and kept for later draws.
+    pub fn draw_with_blend(&mut self, d: Arc<dyn Drawable>, id: ShaderId, blend: BlendMode, draw_info: DrawInfo) -> Result<()> {
+        let key = match d.batch_key() {
+            Some(key) => key,
+            None => return self.draw_now(d, id, blend, draw_info),
+        };
+
+        if let Some(batch) = self.frame.batch.as_mut() {
+            if batch.shader == id && batch.blend == blend && batch.key == key {
+                batch.instances.push(draw_info.into());
+                return Ok(());
+            }
+        }
+
+        self.frame.flush_batch()?;
+        self.frame.batch = Some(Batch {
+            drawable: d,
+            shader: id,
+            blend,
+            key,
+            instances: vec![draw_info.into()],
+        });
+
+        Ok(())
+    }
+
+    fn draw_now(&mut self, d: Arc<dyn Drawable>, id: ShaderId, blend: BlendMode, draw_info: DrawInfo) -> Result<()> {
+        self.frame.flush_batch()?;
+
+        let previous = self.frame.pipelines[id].blend_mode();
+        self.frame.pipelines[id].set_blend_mode(blend)?;
+
+        let commands = self
+            .frame
+            .draw_context(id)
+            .and_then(|context| d.draw(&context, draw_info));
+
+        self.frame.pipelines[id].set_blend_mode(previous)?;
+
+        self.frame.execute_draw(commands?)
+    }
+
+    /// Starts an empty `PipelineData` for `draw_data`.
+    pub fn pipeline_data(&self) -> PipelineData {
+        PipelineData::new(self.frame.queue.device().clone())
+    }
+
+    /// Draws `data` with a registered shader, failing if its bindings do not match the
+    /// shader's layout.
+    pub fn draw_data(&mut self, id: ShaderId, data: PipelineData) -> Result<()> {
+        self.frame.flush_batch()?;
 
-        let commands = d.draw(
-            self.frame.queue.clone(),
-            shader_handle,
-            draw_info,
+        let shader_handle = self.frame
+            .pipelines
+            .get(id)
+            .ok_or_else(|| anyhow!("No shader registered with id {}", id))?;
+
+        let mut builder = AutoCommandBufferBuilder::secondary_graphics(
+            self.frame.queue.device().clone(),
+            self.frame.queue.family(),
+            CommandBufferUsage::OneTimeSubmit,
+            shader_handle.pipeline().subpass().clone(),
         )?;
 
-        self.frame.command_buffer.as_mut().unwrap().execute_commands(commands)?;
+        builder
+            .set_viewport(0, [self.frame.viewport()])
+            .set_scissor(0, [self.frame.scissor()]);
+
+        let resources = &self.frame.resources;
+        resources.record_vertices(data.element_count(), data.instance_count);
+        resources.record_descriptor_sets(data.bindings.set_count());
+        resources.record_upload(data.uploaded());
+
+        // Binds the shader's pipeline before drawing.
+        shader_handle.draw(&mut builder, Box::new(data))?;
+        resources.record_pipeline_bind();
+
+        self.frame.execute_draw(builder.build()?)
+    }
+
+    /// Clips every following draw to `rect`, intersected with the current clip rect, until the
+    /// matching `pop_clip`.
+    pub fn push_clip(&mut self, rect: Rect, space: ClipSpace) -> Result<()> {
+        self.frame.flush_batch()?;
+
+        let [left, top, right, bottom] = match space {
+            ClipSpace::Screen => [rect.x, rect.y, rect.x + rect.w, rect.y + rect.h],
+            ClipSpace::World => self.frame.world_to_screen(rect),
+        };
+
+        let current = self.frame.scissor();
+        let clamp = |value: f32, axis: usize| {
+            let min = current.origin[axis];
+            let max = current.origin[axis] + current.dimensions[axis];
+            (value.max(0.0).round() as u32).clamp(min, max)
+        };
+
+        let origin = [clamp(left, 0), clamp(top, 1)];
+        let end = [clamp(right, 0), clamp(bottom, 1)];
+
+        self.frame.clips.push(Scissor {
+            origin,
+            dimensions: [end[0].saturating_sub(origin[0]), end[1].saturating_sub(origin[1])],
+        });
 
         Ok(())
     }
+
+    /// Restores the clip rect from before the last `push_clip`.
+    pub fn pop_clip(&mut self) -> Result<()> {
+        self.frame.flush_batch()?;
+
+        self.frame
+            .clips
+            .pop()
+            .map(|_| ())
+            .ok_or_else(|| anyhow!("pop_clip called without a matching push_clip"))
+    }
+
+    /// Draws into `view` with its camera until the next `set_view`, or into the whole
+    /// framebuffer with the frame's camera when `view` is `None`.
+    pub fn set_view(&mut self, view: Option<View>) -> Result<()> {
+        self.frame.flush_batch()?;
+        self.frame.view = view;
+
+        Ok(())
+    }
+
+    /// Runs `draw` once for each view with the view set, then goes back to drawing into the
+    /// whole framebuffer so a HUD can be drawn over all views.
+    ///
+    /// # Examples
+    /// ```ignore
+    /// let views = View::split_screen(vec![player_one_camera, player_two_camera]);
+    ///
+    /// pass.draw_views(&views, |pass, _| {
+    ///     for sprite in &world {
+    ///         pass.draw_with(sprite.image.clone(), sprite_shader, sprite.info)?;
+    ///     }
+    ///     Ok(())
+    /// })?;
+    ///
+    /// pass.draw_with(hud.clone(), sprite_shader, DrawInfo::default())?;
+    /// ```
+    pub fn draw_views<F>(&mut self, views: &[View], mut draw: F) -> Result<()>
+    where
+        F: FnMut(&mut Self, &View) -> Result<()>,
+    {
+        let clips = self.frame.clips.len();
+
+        for view in views {
+            self.set_view(Some(view.clone()))?;
+            let result = draw(self, view).and_then(|_| self.frame.flush_batch());
+            // Clips left pushed by a view do not carry over to the next.
+            self.frame.clips.truncate(clips);
+
+            if result.is_err() {
+                self.set_view(None)?;
+                return result;
+            }
+        }
+
+        self.set_view(None)
+    }
+
+    /// Records any queued draws now.
+    pub fn flush(&mut self) -> Result<()> {
+        self.frame.flush_batch()
+    }
 }
\ No newline at end of file
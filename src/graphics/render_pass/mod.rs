@@ -1,17 +1,23 @@
-use vulkano::image::ImageViewAbstract;
+use vulkano::format::ClearValue;
+use vulkano::sync::PipelineStage;
+use vulkano::image::{view::ImageView, AttachmentImage, ImageAccess, ImageUsage, ImageViewAbstract, SampleCount};
 use vulkano::device::Queue;
 use vulkano::sync::GpuFuture;
 use crate::graphics::camera::Camera;
+use crate::graphics::debug;
+use crate::graphics::resources::FrameResources;
 use crate::graphics::shader::{Shader, ShaderId, ShaderHandle, ShaderProgram};
 
 use vulkano::{
-    render_pass::{Framebuffer, FramebufferCreateInfo},
+    render_pass::{AttachmentDescription, Framebuffer, FramebufferCreateInfo, LoadOp, StoreOp},
     command_buffer::{
         AutoCommandBufferBuilder, CommandBufferUsage, SubpassContents,
     },
 };
 
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use anyhow::*;
 
@@ -20,83 +26,312 @@ use crate::graphics::BlendMode;
 
 pub mod frame;
 
+/// How often a render pass watching its shaders checks their files for changes.
+pub const SHADER_WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The shaders `RenderPass::reload_shaders` rebuilt and the ones that failed to. Compile errors
+/// are a `GameError::ShaderCompileError`, reach it with `downcast_ref`.
+#[derive(Debug, Default)]
+pub struct ShaderReload {
+    pub reloaded: Vec<ShaderId>,
+    pub failed: Vec<(ShaderId, Error)>,
+}
+
 pub struct RenderPass {
     queue: Arc<Queue>,
+    resources: Arc<FrameResources>,
     shaders: Vec<Box<dyn ShaderHandle>>,
+    names: HashMap<String, ShaderId>,
     pub render_pass: Arc<vulkano::render_pass::RenderPass>,
+    watch_shaders: bool,
+    // When the shader files were last checked for changes.
+    shaders_checked: Option<Instant>,
+    // Failures of reloads started by watching the shaders, until `take_shader_errors`.
+    shader_errors: Vec<(ShaderId, Error)>,
+    // The attachment the image passed to `frame` is bound to.
+    final_attachment: usize,
+    // Images for every other attachment, such as multisampled color and depth, recreated
+    // when the frame size changes. They are transient unless the render pass loads or stores
+    // them.
+    transient: Vec<(usize, Arc<dyn ImageViewAbstract>)>,
+    transient_dimensions: [u32; 2],
+    name: String,
+    // Whether frames record their camera as the one the scene is drawn with, off for the
+    // engine's own passes drawing over it.
+    scene_camera: bool,
 }
 
 impl RenderPass {
-    pub fn new(queue: Arc<Queue>, render_pass: Arc<vulkano::render_pass::RenderPass>) -> Result<RenderPass> {
+    /// Wraps a render pass whose final image is its first single sampled color attachment,
+    /// every other attachment is created and kept by the render pass.
+    pub fn new(queue: Arc<Queue>, resources: Arc<FrameResources>, render_pass: Arc<vulkano::render_pass::RenderPass>) -> Result<RenderPass> {
+        let final_attachment = render_pass
+            .attachments()
+            .iter()
+            .position(|a| a.samples == SampleCount::Sample1 && a.format.is_some_and(|f| f.aspects().color))
+            .ok_or_else(|| anyhow!("The render pass has no single sampled color attachment to draw the final image into"))?;
+
         Ok(Self {
             queue: queue.clone(),
+            resources,
             shaders: Vec::new(),
+            names: HashMap::new(),
             render_pass,
+            watch_shaders: false,
+            shaders_checked: None,
+            shader_errors: Vec::new(),
+            final_attachment,
+            transient: Vec::new(),
+            transient_dimensions: [0, 0],
+            name: "render pass".to_string(),
+            scene_camera: true,
         })
     }
 
     pub fn register_shader<Vd: VertexDefinition + 'static + Sync + Send>(&mut self, shader: Arc<Shader>, v_type: Vd) -> Result<ShaderId> {
+        self.register_shader_with_blend(shader, v_type, BlendMode::Alpha)
+    }
+
+    pub fn register_shader_with_blend<Vd: VertexDefinition + 'static + Sync + Send>(&mut self, shader: Arc<Shader>, v_type: Vd, blend: BlendMode) -> Result<ShaderId> {
         self.shaders.push(
             Box::new(
                 ShaderProgram::new(
                     self.queue.device().clone(),
                     self.render_pass.clone(),
                     v_type,
-                    shader.topology,
-                    shader.vertex.clone(),
-                    shader.fragment.clone(),
-                    BlendMode::Alpha,
-                )
+                    shader,
+                    blend,
+                )?
             )
         );
 
         Ok(self.shaders.len()-1)
     }
 
+    /// Registers a shader that can later be looked up by name with `shader_id`.
+    pub fn register_named_shader<Vd: VertexDefinition + 'static + Sync + Send>(&mut self, name: &str, shader: Arc<Shader>, v_type: Vd) -> Result<ShaderId> {
+        let id = self.register_shader(shader, v_type)?;
+        self.names.insert(name.to_string(), id);
+        debug::set_name(self.queue.device(), &*self.shaders[id].pipeline(), name);
+
+        Ok(id)
+    }
+
+    /// Names the render pass in GPU timings and graphics debuggers.
+    pub fn set_name(&mut self, name: &str) {
+        self.name = name.to_string();
+        debug::set_name(self.queue.device(), &*self.render_pass, name);
+    }
+
+    pub(crate) fn set_scene_camera(&mut self, scene_camera: bool) {
+        self.scene_camera = scene_camera;
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn shader_id(&self, name: &str) -> Option<ShaderId> {
+        self.names.get(name).copied()
+    }
+
+    /// Changes the blend mode a shader draws with by default.
+    pub fn set_blend_mode(&mut self, id: ShaderId, mode: BlendMode) -> Result<()> {
+        self.shader_mut(id)?.set_blend_mode(mode)
+    }
+
+    pub fn set_blend_constants(&mut self, id: ShaderId, constants: [f32; 4]) -> Result<()> {
+        self.shader_mut(id)?.set_blend_constants(constants)
+    }
+
+    fn shader_mut(&mut self, id: ShaderId) -> Result<&mut Box<dyn ShaderHandle>> {
+        self.shaders
+            .get_mut(id)
+            .ok_or_else(|| anyhow!("No shader registered with id {}", id))
+    }
+
+    /// When enabled, `frame` checks whether the files of shaders loaded with
+    /// `Shader::from_spirv_files` changed on disk and rebuilds their pipelines, at most once
+    /// every `SHADER_WATCH_INTERVAL`. Shaders that fail to rebuild are kept for
+    /// `take_shader_errors`.
+    pub fn watch_shaders(&mut self, enabled: bool) {
+        self.watch_shaders = enabled;
+    }
+
+    /// Rebuilds every shader whose files changed on disk.
+    ///
+    /// A shader that fails to rebuild keeps its previous pipelines.
+    pub fn reload_shaders(&mut self) -> ShaderReload {
+        let mut result = ShaderReload::default();
+
+        for (id, shader) in self.shaders.iter_mut().enumerate() {
+            match shader.reload() {
+                Result::Ok(true) => result.reloaded.push(id),
+                Result::Ok(false) => {}
+                Err(e) => result.failed.push((id, e)),
+            }
+        }
+
+        result
+    }
+
+    /// Takes the errors of shaders that failed to rebuild while watching them, oldest first.
+    pub fn take_shader_errors(&mut self) -> Vec<(ShaderId, Error)> {
+        std::mem::take(&mut self.shader_errors)
+    }
+
+    fn create_transient(&mut self, dimensions: [u32; 2]) -> Result<()> {
+        let mut transient = Vec::new();
+
+        for (i, a) in self.render_pass.attachments().iter().enumerate() {
+            if i == self.final_attachment {
+                continue;
+            }
+
+            let format = a.format.ok_or_else(|| anyhow!("Attachment {} has no format", i))?;
+            let aspects = format.aspects();
+            let usage = ImageUsage {
+                color_attachment: aspects.color,
+                depth_stencil_attachment: aspects.depth || aspects.stencil,
+                input_attachment: self
+                    .render_pass
+                    .subpasses()
+                    .iter()
+                    .flat_map(|s| s.input_attachments.iter().flatten())
+                    .any(|r| r.attachment as usize == i),
+                // Only attachments that are neither loaded nor stored can live in tile memory.
+                transient_attachment: a.load_op != LoadOp::Load
+                    && a.stencil_load_op != LoadOp::Load
+                    && a.store_op == StoreOp::DontCare
+                    && a.stencil_store_op == StoreOp::DontCare,
+                ..ImageUsage::none()
+            };
+            let image = AttachmentImage::multisampled_with_usage(
+                self.queue.device().clone(),
+                dimensions,
+                a.samples,
+                format,
+                usage,
+            )?;
+            debug::set_name(self.queue.device(), image.inner().image, &format!("render pass attachment {}", i));
+
+            transient.push((i, ImageView::new_default(image)? as Arc<dyn ImageViewAbstract>));
+        }
+
+        self.transient = transient;
+        self.transient_dimensions = dimensions;
+
+        Result::Ok(())
+    }
+
+    /// Starts recording a frame that draws into `final_image`.
+    ///
+    /// `camera` is the matrix `Image`, `TextureArray` and `Mesh` draws are transformed by, they
+    /// no longer draw with an identity matrix regardless of the camera. Pass
+    /// `OrthographicCamera::default()` to keep drawing directly in clip space.
     pub fn frame(&mut self,
         clear_color: [f32; 4],
         before_future: Box<dyn GpuFuture + 'static>,
         final_image: Arc<dyn ImageViewAbstract + 'static>,
-        _camera: Arc<dyn Camera>,
-    ) -> Result<frame::Frame>
+        camera: Arc<dyn Camera>,
+    ) -> Result<frame::Frame<'_>>
     {
-        let _img_dims = final_image.image().dimensions().width_height();
+        if self.watch_shaders
+            && self.shaders_checked.map_or(true, |checked| checked.elapsed() >= SHADER_WATCH_INTERVAL)
+        {
+            self.shaders_checked = Some(Instant::now());
+            let failed = self.reload_shaders().failed;
+            self.shader_errors.extend(failed);
+        }
+
+        if self.scene_camera {
+            self.resources.set_camera_mvp(camera.as_mvp());
+        }
+
+        let dimensions = final_image.image().dimensions().width_height();
+
+        if self.transient_dimensions != dimensions {
+            self.create_transient(dimensions)?;
+        }
+
+        let mut attachments = vec![final_image; self.render_pass.attachments().len()];
+        for (i, image) in &self.transient {
+            attachments[*i] = image.clone();
+        }
 
         let framebuffer = Framebuffer::new(
            self.render_pass.clone(),
             FramebufferCreateInfo {
-                attachments: vec![final_image],
+                attachments,
                 ..Default::default()
             },
         )?;
 
+        let clear_values = self
+            .render_pass
+            .attachments()
+            .iter()
+            .map(|a| clear_value(a, clear_color))
+            .collect::<Vec<_>>();
+
         let mut command_buffer = AutoCommandBufferBuilder::primary(
             self.queue.device().clone(),
             self.queue.family(),
             CommandBufferUsage::OneTimeSubmit,
         )?;
 
+        let num_pass = self.render_pass.subpasses().len() as u8;
+
+        let timestamps = self.resources.reserve_timestamps(&self.name, num_pass);
+        if let Some(queries) = &timestamps {
+            unsafe {
+                command_buffer
+                    .reset_query_pool(queries.pool.clone(), queries.range())?
+                    .write_timestamp(queries.pool.clone(), queries.frame_start(), PipelineStage::TopOfPipe)?;
+            }
+        }
+
         command_buffer.begin_render_pass(
             framebuffer.clone(),
             SubpassContents::SecondaryCommandBuffers,
-            vec![clear_color.into()],
+            clear_values,
         )?;
 
         // if render_pass.subpasses().len() > 16 {
         //     return Err(i)
         // }
 
-        let num_pass = self.render_pass.subpasses().len() as u8;
-
         Ok(frame::Frame {
-            pipelines: &self.shaders,
+            pipelines: &mut self.shaders,
             before_main_cb_future: Some(before_future),
             // framebuffer,
             queue: self.queue.clone(),
+            resources: self.resources.clone(),
             num_pass,
+            dimensions,
             cur_pass: 0,
             command_buffer: Some(command_buffer),
-            // camera,
+            batch: None,
+            camera,
+            clips: Vec::new(),
+            view: None,
+            render_pass: self.render_pass.clone(),
+            timestamps,
         })
     }
-}
\ No newline at end of file
+}
+
+// Color attachments clear to `clear_color`, depth to the far plane and stencil to 0.
+fn clear_value(attachment: &AttachmentDescription, clear_color: [f32; 4]) -> ClearValue {
+    if attachment.load_op != LoadOp::Clear {
+        return ClearValue::None;
+    }
+
+    let aspects = attachment.format.map(|f| f.aspects()).unwrap_or_default();
+    match (aspects.depth, aspects.stencil) {
+        (true, true) => ClearValue::DepthStencil((1.0, 0)),
+        (true, false) => ClearValue::Depth(1.0),
+        (false, true) => ClearValue::Stencil(0),
+        (false, false) => clear_color.into(),
+    }
+}
@@ -1,14 +1,14 @@
 use vulkano::{
-    command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer},
-    device::physical::{PhysicalDevice, PhysicalDeviceType},
-    device::{Device, DeviceCreateInfo, DeviceExtensions, QueueCreateInfo},
-    image::{view::{ImageView, ImageViewCreateInfo}, ImageUsage, SwapchainImage},
+    command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer, PrimaryCommandBuffer},
+    device::physical::PhysicalDevice,
+    device::{Device, DeviceCreateInfo, DeviceExtensions, DeviceOwned, Queue, QueueCreateInfo},
+    image::{view::{ImageView, ImageViewCreateInfo}, AttachmentImage, ImageAccess, ImageUsage, ImageViewAbstract, SampleCount, SampleCounts, SwapchainImage},
     instance::{Instance, InstanceCreateInfo},
     sampler::{Filter, Sampler, SamplerAddressMode, SamplerCreateInfo},
     swapchain::{self, Surface, PresentMode, AcquireError, Swapchain, SwapchainCreateInfo, SwapchainCreationError},
-    sync::{self, FlushError, GpuFuture},
+    sync::{self, FenceSignalFuture, FlushError, GpuFuture},
     Version,
-    format::Format,
+    format::{ClearValue, Format},
 };
 
 use vulkano_win::VkSurfaceBuild;
@@ -21,12 +21,16 @@ use anyhow::{Result, anyhow};
 
 use crate::{
     conf::*, 
-    graphics::shader::ShaderId, 
+    graphics::bindings::Bindings,
+    graphics::shader::{ComputeId, ComputeProgram, ShaderId},
     graphics::*,
 };
 
 pub type FinalImageView = Arc<ImageView<SwapchainImage<Window>>>;
 
+// Signaled when the GPU finishes a submitted frame.
+type FrameFence = Arc<FenceSignalFuture<Box<dyn GpuFuture>>>;
+
 pub struct Renderer {
     pub queue: Arc<vulkano::device::Queue>,
     pub(crate) surface: Arc<vulkano::swapchain::Surface<winit::window::Window>>,
@@ -36,24 +40,59 @@ pub struct Renderer {
     pub(crate) image_num: usize,
     pub(crate) recreate_swapchain: bool,
     pub previous_frame_end: Option<Box<dyn vulkano::sync::GpuFuture>>,
+    // The start of the frame handed out by `begin_frame` until it is ended or cancelled, kept
+    // so the acquired image can still be presented when the frame fails.
+    frame_start: Option<FrameFence>,
+    /// The fence of the last frame submitted from each frame slot, waited on before the slot's
+    /// resources are reused.
+    frame_fences: Vec<Option<FrameFence>>,
     pub present_future: Option<Box<dyn vulkano::sync::GpuFuture>>,
     pub command_buffer: Option<AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>>,
+    /// The built-in sprite shader on the default render pass.
     pub default_shader: ShaderId,
+    /// Render passes drawing to the swapchain, the first one is created with the renderer and
+    /// has every shader from `graphics::library` registered on it.
     pub render_passes: Vec<render_pass::RenderPass>,
     pub samplers: Vec<Arc<Sampler>>,
+    /// Buffer pools and descriptor set caches shared by every render pass.
+    pub resources: Arc<resources::FrameResources>,
+    pub compute_programs: Vec<ComputeProgram>,
+    /// Samples per pixel of the default render pass, the count from `WindowSetup` clamped to
+    /// what the device supports for color attachments. Use it as `samples as u32` to create
+    /// matching render passes, checking `framebuffer_depth_sample_counts` when they add depth.
+    pub samples: SampleCount,
+    /// The device the renderer draws with, chosen by the `DevicePreference` in `Conf`.
+    pub adapter: adapter::AdapterInfo,
+    /// Uploads textures without stalling frames, on a dedicated transfer queue when the device
+    /// has one.
+    pub uploader: upload::Uploader,
+    /// What the last frame submitted with `end_frame` cost.
+    pub stats: resources::RenderStats,
+    // Prints validation messages while it is alive.
+    #[allow(unused)]
+    debug_callback: Option<vulkano::instance::debug::DebugCallback>,
+    /// The logical resolution from `Conf` and the image drawn at it, which is scaled into the
+    /// swapchain image at the end of every frame.
+    pub(crate) virtual_target: Option<(VirtualResolution, Arc<ImageView<AttachmentImage>>)>,
+    // Draws the virtual resolution target into the swapchain image when the surface does not
+    // support blitting into it.
+    present_pass: Option<PresentPass>,
 }
 
 impl Renderer {
-    pub fn new(_conf: Conf) -> (Self, winit::event_loop::EventLoop<()>) {
+    pub fn new(conf: Conf) -> (Self, winit::event_loop::EventLoop<()>) {
         let required_extensions = vulkano_win::required_extensions();
         let instance = Instance::new(InstanceCreateInfo {
             application_name: None,
             application_version: Version::V1_1,
-            enabled_extensions: required_extensions,
+            enabled_extensions: required_extensions.union(&debug::extensions(conf.debug)),
+            enabled_layers: debug::layers(conf.debug),
             ..Default::default()
         })
         .unwrap();
 
+        let debug_callback = debug::messenger(&instance, conf.debug);
+
         let event_loop = EventLoop::new();
         let surface = WindowBuilder::new()
             .build_vk_surface(&event_loop, instance.clone())
@@ -63,29 +102,21 @@ impl Renderer {
             khr_swapchain: true,
             ..DeviceExtensions::none()
         };
-        let (physical_device, queue_family) = PhysicalDevice::enumerate(&instance)
-            .filter(|&p| p.supported_extensions().is_superset_of(&device_extensions))
-            .filter_map(|p| {
-                p.queue_families()
-                    .find(|&q| {
-                        q.supports_graphics() && q.supports_surface(&surface).unwrap_or(false)
-                    })
-                    .map(|q| (p, q))
-            })
-            .min_by_key(|(p, _)| match p.properties().device_type {
-                PhysicalDeviceType::DiscreteGpu => 0,
-                PhysicalDeviceType::IntegratedGpu => 1,
-                PhysicalDeviceType::VirtualGpu => 2,
-                PhysicalDeviceType::Cpu => 3,
-                PhysicalDeviceType::Other => 4,
-            })
-            .unwrap();
-
-        println!(
-            "Using device: {} (type: {:?})",
-            physical_device.properties().device_name,
-            physical_device.properties().device_type,
-        );
+        let (physical_device, queue_family) =
+            adapter::select(&instance, &surface, &device_extensions, &conf.device).unwrap();
+        let adapter = adapter::AdapterInfo::new(physical_device);
+
+        // A family that only does transfers is usually backed by a copy engine that runs
+        // alongside rendering.
+        let transfer_family = physical_device
+            .queue_families()
+            .filter(|q| q.explicitly_supports_transfers() && !q.supports_graphics())
+            .min_by_key(|q| q.supports_compute());
+
+        let mut queue_create_infos = vec![QueueCreateInfo::family(queue_family)];
+        if let Some(family) = transfer_family {
+            queue_create_infos.push(QueueCreateInfo::family(family));
+        }
 
         let (device, mut queues) = Device::new(
             physical_device,
@@ -93,19 +124,39 @@ impl Renderer {
                 enabled_extensions: physical_device
                     .required_extensions()
                     .union(&device_extensions),
-                queue_create_infos: vec![QueueCreateInfo::family(queue_family)],
+                queue_create_infos,
                 ..Default::default()
             },
         )
         .unwrap();
 
         let queue = queues.next().unwrap();
+        let transfer_queue = queues.next().unwrap_or_else(|| queue.clone());
+
+        // Frames rendered at a virtual resolution are blitted into the swapchain image when the
+        // surface and the format allow it, and drawn in with a render pass when they don't. The
+        // virtual target has the format of the swapchain images.
+        let blit_present = conf.virtual_resolution.is_some_and(|resolution| {
+            let features = physical_device
+                .format_properties(Self::surface_format(physical_device, &surface))
+                .optimal_tiling_features;
+
+            physical_device
+                .surface_capabilities(&surface, Default::default())
+                .unwrap()
+                .supported_usage_flags
+                .transfer_destination
+                && features.blit_src
+                && features.blit_dst
+                && (resolution.scaling == ScalingMode::Integer || features.sampled_image_filter_linear)
+        });
 
         let (swapchain, images) = Self::create_swap_chain(
             surface.clone(),
             physical_device,
             device.clone(),
-            PresentMode::Immediate
+            PresentMode::Immediate,
+            blit_present,
         ).unwrap();
 
         let default_future = Some(sync::now(device.clone()).boxed());
@@ -125,7 +176,89 @@ impl Renderer {
 
         samplers.push(default_sampler);
 
-        return (Renderer {
+        let resources =
+            resources::FrameResources::new(queue.clone(), conf.frames_in_flight.max(1)).unwrap();
+
+        let samples = Self::supported_samples(physical_device, conf.window_setup.samples);
+
+        let default_render_pass = if samples == SampleCount::Sample1 {
+            vulkano::single_pass_renderpass!(device.clone(),
+                attachments: {
+                    color: {
+                        load: Clear,
+                        store: Store,
+                        format: swapchain.image_format(),
+                        samples: 1,
+                    }
+                },
+                pass: {
+                    color: [color],
+                    depth_stencil: {}
+                }
+            )
+        } else {
+            // Drawn into a multisampled image that is resolved into the final image.
+            vulkano::single_pass_renderpass!(device.clone(),
+                attachments: {
+                    multisampled: {
+                        load: Clear,
+                        store: DontCare,
+                        format: swapchain.image_format(),
+                        samples: samples as u32,
+                    },
+                    color: {
+                        load: DontCare,
+                        store: Store,
+                        format: swapchain.image_format(),
+                        samples: 1,
+                    }
+                },
+                pass: {
+                    color: [multisampled],
+                    depth_stencil: {},
+                    resolve: [color],
+                }
+            )
+        };
+
+        let mut default_pass = render_pass::RenderPass::new(
+            queue.clone(),
+            resources.clone(),
+            default_render_pass.unwrap(),
+        )
+        .unwrap();
+
+        default_pass.set_name("default");
+        let default_shader = library::register(device.clone(), &mut default_pass).unwrap();
+
+        let virtual_target = conf.virtual_resolution.map(|resolution| {
+            let image = AttachmentImage::with_usage(
+                device.clone(),
+                [resolution.width, resolution.height],
+                swapchain.image_format(),
+                ImageUsage {
+                    color_attachment: true,
+                    sampled: true,
+                    transfer_source: true,
+                    ..ImageUsage::none()
+                },
+            )
+            .unwrap();
+
+            debug::set_name(&device, image.inner().image, "virtual resolution target");
+
+            (resolution, ImageView::new_default(image).unwrap())
+        });
+
+        let present_pass = match conf.virtual_resolution {
+            Some(resolution) if !blit_present => Some(
+                PresentPass::new(queue.clone(), resources.clone(), swapchain.image_format(), resolution.scaling)
+                    .unwrap(),
+            ),
+            _ => None,
+        };
+
+        (Renderer {
             queue,
             surface,
             device,
@@ -134,31 +267,62 @@ impl Renderer {
             image_views: images,
             present_future: None,
             previous_frame_end: default_future,
+            frame_fences: (0..resources.frames_in_flight()).map(|_| None).collect(),
+            frame_start: None,
             recreate_swapchain: false,
             command_buffer: None,
-            default_shader: 0,
+            default_shader,
             samplers,
-            render_passes: Vec::new(),
-        }, event_loop);
+            render_passes: vec![default_pass],
+            resources,
+            compute_programs: Vec::new(),
+            samples,
+            adapter,
+            debug_callback,
+            stats: Default::default(),
+            uploader: upload::Uploader::new(transfer_queue),
+            virtual_target,
+            present_pass,
+        }, event_loop)
     }
     
+    // The highest sample count up to `requested` that color attachments support, the default
+    // render pass has no depth attachment.
+    fn supported_samples(physical: PhysicalDevice, requested: u32) -> SampleCount {
+        let properties = physical.properties();
+        let supports = |counts: SampleCounts, samples: u32| match samples {
+            2 => counts.sample2,
+            4 => counts.sample4,
+            8 => counts.sample8,
+            16 => counts.sample16,
+            32 => counts.sample32,
+            64 => counts.sample64,
+            _ => true,
+        };
+
+        let samples = [64, 32, 16, 8, 4, 2, 1]
+            .into_iter()
+            .filter(|&samples| samples <= requested.max(1))
+            .find(|&samples| {
+                supports(properties.framebuffer_color_sample_counts, samples)
+            })
+            .unwrap_or(1);
+
+        SampleCount::try_from(samples).unwrap()
+    }
+
     fn create_swap_chain(
         surface: Arc<Surface<Window>>,
         physical: PhysicalDevice,
         device: Arc<Device>,
         present_mode: PresentMode,
+        transfer_destination: bool,
     ) -> Result<(Arc<Swapchain<Window>>, Vec<FinalImageView>)> {
         let caps = physical
             .surface_capabilities(&surface, Default::default())
             .unwrap();
 
-        // Choosing the internal format that the images will have.
-        let image_format = Some(
-            physical
-                .surface_formats(&surface, Default::default())
-                .unwrap()[0]
-                .0,
-        );
+        let image_format = Some(Self::surface_format(physical, &surface));
 
         let (swapchain, images) = {
             Swapchain::new(
@@ -169,7 +333,11 @@ impl Renderer {
                     image_format: image_format,
                     present_mode,
                     image_extent: surface.window().inner_size().into(),
-                    image_usage: ImageUsage::color_attachment(),
+                    image_usage: ImageUsage {
+                        color_attachment: true,
+                        transfer_destination,
+                        ..ImageUsage::none()
+                    },
                     composite_alpha: caps
                         .supported_composite_alpha
                         .iter()
@@ -188,48 +356,75 @@ impl Renderer {
                 ImageView::new(image, info).unwrap()
             })
             .collect::<Vec<_>>();
+        Self::name_swapchain_images(&device, &images);
+
         Ok((swapchain, images))
     }
 
+    // The format the swapchain images are created with.
+    fn surface_format(physical: PhysicalDevice, surface: &Surface<Window>) -> Format {
+        physical
+            .surface_formats(surface, Default::default())
+            .unwrap()[0]
+            .0
+    }
+
+    fn name_swapchain_images(device: &Device, images: &[FinalImageView]) {
+        for (i, view) in images.iter().enumerate() {
+            debug::set_name(device, view.image().inner().image, &format!("swapchain image {}", i));
+        }
+    }
+
     /// Handles setup of a new frame, called when the graphics pipeline is first created and
     /// at the end of every frame to start the next one.
     ///
     /// This is necessary because the swapchain could be out of date,
     /// as well as updating the image_num, optimality, and the swapcahin future.
-    pub fn begin_frame(&mut self) -> Result<Box<dyn GpuFuture>> {
+    ///
+    /// Returns `None` when no frame can be drawn right now, while the window is minimized or
+    /// the swapchain is being recreated, and the frame should be skipped. Recreation is retried
+    /// on the next call. A frame that is started has to be passed to `end_frame` or
+    /// `cancel_frame`.
+    pub fn begin_frame(&mut self) -> Result<Option<FrameStart>> {
+        // The last frame was neither ended nor cancelled, its image still has to be presented
+        // before another one is acquired.
+        if self.frame_start.is_some() {
+            self.present_unfinished();
+        }
+
         self.previous_frame_end.as_mut().unwrap().cleanup_finished();
 
-        if self.recreate_swapchain {
-            let (new_swapchain, new_images) = match self.swapchain.recreate(SwapchainCreateInfo {
-                image_extent: self.surface.window().inner_size().into(),
-                ..self.swapchain.create_info()
-            }) {
-                Ok(r) => r,
-                Err(SwapchainCreationError::ImageExtentNotSupported { .. }) => {return Err(anyhow!(AcquireError::OutOfDate))},
-                Err(e) => panic!("Failed to recreate swapchain: {:?}", e),
-            };
+        if self.is_minimized() {
+            return Ok(None);
+        }
 
-            let new_images = new_images
-            .into_iter()
-            .map(|image| {
-                let info = ImageViewCreateInfo::from_image(&image);
-                ImageView::new(image, info).unwrap()
-            })
-            .collect::<Vec<_>>();
+        if self.recreate_swapchain && !self.recreate_swapchain_images()? {
+            return Ok(None);
+        }
 
-            self.image_views = new_images;
-            self.swapchain = new_swapchain;
-            self.recreate_swapchain = false;
+        // Blocks until the GPU has finished the oldest frame in flight.
+        if let Some(fence) = self.frame_fences[self.resources.next_slot()].take() {
+            if let Err(e) = fence.wait(None) {
+                println!("Failed to wait for frame fence: {:?}", e);
+            }
         }
 
         let (image_num, suboptimal, acquire_future) =
             match swapchain::acquire_next_image(self.swapchain.clone(), None) {
                 Ok(r) => r,
-                Err(AcquireError::OutOfDate) => {
+                Err(AcquireError::OutOfDate) | Err(AcquireError::FullScreenExclusiveLost) => {
+                    self.recreate_swapchain = true;
+                    return Ok(None);
+                }
+                Err(AcquireError::SurfaceLost) => {
+                    // The window owns the surface, so the best that can be done is to keep
+                    // trying to build a swapchain for it.
+                    println!("Lost the window surface, recreating the swapchain");
                     self.recreate_swapchain = true;
-                    return Err(anyhow!(AcquireError::OutOfDate));
+                    return Ok(None);
                 }
-                Err(e) => panic!("Failed to acquire next image: {:?}", e),
+                Err(AcquireError::Timeout) => return Ok(None),
+                Err(e) => return Err(anyhow!("Failed to acquire next image: {:?}", e)),
             };
 
         if suboptimal {
@@ -237,8 +432,68 @@ impl Renderer {
         }
 
         self.image_num = image_num;
+        self.resources.begin_frame();
+
+        let mut future = self.previous_frame_end.take().unwrap().join(acquire_future).boxed();
+        if let Some(uploads) = self.uploader.flush() {
+            future = future.join(uploads).boxed();
+        }
 
-        Ok(self.previous_frame_end.take().unwrap().join(acquire_future).boxed())
+        // Flushed right away so a cancelled frame can still be presented after the acquire.
+        match future.then_signal_fence_and_flush() {
+            Ok(future) => {
+                #[allow(clippy::arc_with_non_send_sync)]
+                let start = Arc::new(future);
+                self.frame_start = Some(start.clone());
+                Ok(Some(FrameStart { start }))
+            }
+            Err(FlushError::OutOfDate)
+            | Err(FlushError::SurfaceLost)
+            | Err(FlushError::FullScreenExclusiveLost) => {
+                self.recreate_swapchain = true;
+                self.previous_frame_end = Some(sync::now(self.device.clone()).boxed());
+                Ok(None)
+            }
+            Err(e) => {
+                self.previous_frame_end = Some(sync::now(self.device.clone()).boxed());
+                Err(anyhow!("Failed to start the frame: {:?}", e))
+            }
+        }
+    }
+
+    /// Whether the window has no area to draw to, such as while it is minimized.
+    pub fn is_minimized(&self) -> bool {
+        let size = self.surface.window().inner_size();
+        size.width == 0 || size.height == 0
+    }
+
+    // Returns false when the surface cannot have a swapchain right now, to be retried later.
+    fn recreate_swapchain_images(&mut self) -> Result<bool> {
+        let (new_swapchain, new_images) = match self.swapchain.recreate(SwapchainCreateInfo {
+            image_extent: self.surface.window().inner_size().into(),
+            ..self.swapchain.create_info()
+        }) {
+            Ok(r) => r,
+            // The window is being resized or minimized, or the surface was lost.
+            Err(SwapchainCreationError::ImageExtentNotSupported { .. })
+            | Err(SwapchainCreationError::SurfaceLost) => return Ok(false),
+            Err(e) => return Err(anyhow!("Failed to recreate swapchain: {:?}", e)),
+        };
+
+        let new_images = new_images
+            .into_iter()
+            .map(|image| {
+                let info = ImageViewCreateInfo::from_image(&image);
+                ImageView::new(image, info)
+            })
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Self::name_swapchain_images(&self.device, &new_images);
+
+        self.image_views = new_images;
+        self.swapchain = new_swapchain;
+        self.recreate_swapchain = false;
+
+        Ok(true)
     }
 
     /// This function submits the command buffer to the queue and fences the operation,
@@ -246,19 +501,49 @@ impl Renderer {
     ///
     /// This function must be run once at the end of all updates and draw calls in order for the frame to be sumbitted.
     pub fn end_frame(&mut self, after_future: Box<dyn GpuFuture>) {
+        self.stats = self.resources.stats();
+
+        let after_future = match &self.virtual_target {
+            Some(_) => match self.present_virtual(after_future) {
+                Ok(future) => future,
+                Err(e) => {
+                    // The image was acquired, so it has to be presented even though the blit
+                    // failed.
+                    println!("Failed to scale the virtual resolution target: {:?}", e);
+                    self.present_unfinished();
+                    return;
+                }
+            },
+            None => after_future,
+        };
+
+        self.frame_start = None;
+        self.present(after_future);
+    }
+
+    // Presents the swapchain image after `after_future` and fences the frame.
+    fn present(&mut self, after_future: Box<dyn GpuFuture>) {
         let future = after_future
             .then_swapchain_present(
                 self.queue.clone(),
                 self.swapchain.clone(),
                 self.image_num,
             )
+            .boxed()
             .then_signal_fence_and_flush();
 
         match future {
             Ok(future) => {
+                // Frame futures never leave this thread, but vulkano only implements
+                // `GpuFuture` for fences shared through an `Arc`.
+                #[allow(clippy::arc_with_non_send_sync)]
+                let future = Arc::new(future);
+                self.frame_fences[self.resources.current_slot()] = Some(future.clone());
                 self.previous_frame_end = Some(future.boxed());
             }
-            Err(FlushError::OutOfDate) => {
+            Err(FlushError::OutOfDate)
+            | Err(FlushError::SurfaceLost)
+            | Err(FlushError::FullScreenExclusiveLost) => {
                 self.recreate_swapchain = true;
                 self.previous_frame_end = Some(sync::now(self.device.clone()).boxed());
             }
@@ -269,11 +554,296 @@ impl Renderer {
         };
     }
 
-    pub fn final_image(&self) -> FinalImageView {
-        self.image_views[self.image_num].clone()
+    /// Ends a frame started with `begin_frame` that could not be drawn by presenting a cleared
+    /// image, so the swapchain image it acquired is given back. Does nothing when the frame was
+    /// already passed to `end_frame`.
+    pub fn cancel_frame(&mut self, frame: FrameStart) {
+        if self.frame_start.as_ref().is_some_and(|start| Arc::ptr_eq(start, &frame.start)) {
+            self.present_unfinished();
+        }
+    }
+
+    // Runs the default render pass into `image` without drawing anything, clearing it.
+    fn clear_frame(&mut self, before_future: Box<dyn GpuFuture>, image: Arc<dyn ImageViewAbstract>) -> Result<Box<dyn GpuFuture>> {
+        let camera = Arc::new(camera::OrthographicCamera::default());
+        let mut frame = self.render_passes[0].frame([0.0, 0.0, 0.0, 1.0], before_future, image, camera)?;
+
+        loop {
+            match frame.next_pass()? {
+                Some(render_pass::frame::PassState::DrawPass(_)) => {}
+                Some(render_pass::frame::PassState::Finished(future)) => return Ok(future),
+                None => return Err(anyhow!("The default render pass finished without a future")),
+            }
+        }
+    }
+
+    // Clears the acquired swapchain image after the start of the frame and presents it, used
+    // when the frame can no longer be submitted normally.
+    fn present_unfinished(&mut self) {
+        let start = match self.frame_start.take() {
+            Some(start) => start,
+            None => return,
+        };
+
+        let image = self.image_views[self.image_num].clone();
+        let future = match self.clear_frame(Box::new(start.clone()), image) {
+            Ok(future) => future,
+            Err(e) => {
+                println!("Failed to clear the cancelled frame: {:?}", e);
+                Box::new(start)
+            }
+        };
+
+        self.present(future);
+    }
+
+    /// Scales the virtual resolution target into the swapchain image, clearing the bars
+    /// around it.
+    fn present_virtual(&mut self, after_future: Box<dyn GpuFuture>) -> Result<Box<dyn GpuFuture>> {
+        let (resolution, target) = self.virtual_target.as_ref().unwrap();
+        let extent = self.swapchain.image_extent();
+        let rect = resolution.present_rect(extent);
+
+        if let Some(present_pass) = &mut self.present_pass {
+            return present_pass.draw(
+                after_future,
+                target.clone(),
+                self.image_views[self.image_num].clone(),
+                extent,
+                rect,
+            );
+        }
+
+        let swapchain_image = self.image_views[self.image_num].image().clone();
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            self.device.clone(),
+            self.queue.family(),
+            CommandBufferUsage::OneTimeSubmit,
+        )?;
+
+        builder.clear_color_image(swapchain_image.clone(), ClearValue::Float(Color::black().into()))?;
+
+        if let Some([x, y, w, h]) = rect {
+            let filter = match resolution.scaling {
+                ScalingMode::Integer => Filter::Nearest,
+                _ => Filter::Linear,
+            };
+
+            builder.blit_image(
+                target.image().clone(),
+                [0, 0, 0],
+                [resolution.width as i32, resolution.height as i32, 1],
+                0,
+                0,
+                swapchain_image,
+                [x as i32, y as i32, 0],
+                [(x + w) as i32, (y + h) as i32, 1],
+                0,
+                0,
+                1,
+                filter,
+            )?;
+        }
+
+        let command_buffer = builder.build()?;
+        debug::set_name(&self.device, command_buffer.inner(), "virtual resolution blit");
+
+        Ok(after_future.then_execute(self.queue.clone(), command_buffer)?.boxed())
+    }
+
+    /// The image to draw the frame into, the swapchain image or the virtual resolution target
+    /// when one is configured.
+    pub fn final_image(&self) -> Arc<dyn ImageViewAbstract> {
+        match &self.virtual_target {
+            Some((_, target)) => target.clone(),
+            None => self.image_views[self.image_num].clone(),
+        }
+    }
+
+    /// The ratio of physical pixels to logical points of the window's display.
+    pub fn scale_factor(&self) -> f64 {
+        self.surface.window().scale_factor()
+    }
+
+    /// The size of the window's drawable area in physical pixels.
+    pub fn window_size(&self) -> [u32; 2] {
+        self.surface.window().inner_size().into()
+    }
+
+    /// The size of `final_image` in pixels.
+    pub fn dimensions(&self) -> [u32; 2] {
+        match &self.virtual_target {
+            Some((resolution, _)) => [resolution.width, resolution.height],
+            None => self.swapchain.image_extent(),
+        }
+    }
+
+    /// Maps a position in window pixels to pixels of `final_image`.
+    pub fn to_logical(&self, position: (f64, f64)) -> (f64, f64) {
+        match &self.virtual_target {
+            Some((resolution, _)) => resolution.to_logical(self.swapchain.image_extent(), position),
+            None => position,
+        }
+    }
+
+    /// Looks up a built-in shader on the default render pass by its name in `graphics::library`.
+    pub fn shader(&self, name: &str) -> Option<ShaderId> {
+        self.render_passes[0].shader_id(name)
+    }
+
+    /// Builds a compute pipeline for `module`, which must have a compute entry point named `main`.
+    pub fn register_compute(&mut self, module: Arc<vulkano::shader::ShaderModule>) -> Result<ComputeId> {
+        let program = ComputeProgram::new(self.device.clone(), module)?;
+        debug::set_name(&self.device, &*program.pipeline(), &format!("compute {}", self.compute_programs.len()));
+        self.compute_programs.push(program);
+
+        Ok(self.compute_programs.len() - 1)
+    }
+
+    /// Runs a registered compute shader after `before_future`, pass the returned future on to
+    /// the render pass that uses its output.
+    pub fn dispatch(
+        &self,
+        id: ComputeId,
+        before_future: Box<dyn GpuFuture>,
+        group_counts: [u32; 3],
+        bindings: Bindings,
+    ) -> Result<Box<dyn GpuFuture>> {
+        self.compute_programs
+            .get(id)
+            .ok_or_else(|| anyhow!("No compute shader registered with id {}", id))?
+            .dispatch(self.queue.clone(), before_future, group_counts, bindings)
     }
 
     pub fn output_format(&self) -> Format {
         self.image_views[self.image_num].format().unwrap()
     }
 }
+
+/// A frame started by `Renderer::begin_frame`, after the swapchain image was acquired and any
+/// pending uploads. Draw after `future` and pass the result to `Renderer::end_frame`, or give
+/// the frame to `Renderer::cancel_frame` when it can't be drawn.
+pub struct FrameStart {
+    start: FrameFence,
+}
+
+impl FrameStart {
+    /// A future to draw the frame after, can be taken more than once.
+    pub fn future(&self) -> Box<dyn GpuFuture> {
+        Box::new(self.start.clone())
+    }
+}
+
+// Draws the virtual resolution target as a textured quad, for surfaces whose images can't be
+// blitted into.
+struct PresentPass {
+    render_pass: render_pass::RenderPass,
+    shader: ShaderId,
+    sampler: Arc<Sampler>,
+    camera: Arc<dyn camera::Camera>,
+}
+
+impl PresentPass {
+    fn new(queue: Arc<Queue>, resources: Arc<resources::FrameResources>, format: Format, scaling: ScalingMode) -> Result<Self> {
+        let device = queue.device().clone();
+
+        let render_pass = vulkano::single_pass_renderpass!(device.clone(),
+            attachments: {
+                color: {
+                    load: Clear,
+                    store: Store,
+                    format: format,
+                    samples: 1,
+                }
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {}
+            }
+        )?;
+
+        let mut render_pass = render_pass::RenderPass::new(queue, resources, render_pass)?;
+        render_pass.set_name("virtual resolution present");
+        render_pass.set_scene_camera(false);
+        let shader = render_pass.register_shader(
+            Arc::new(shader::Shader::new(
+                vs::load(device.clone())?,
+                fs::load(device.clone())?,
+                shader::VertexTopology::TriangleStrip,
+            )),
+            library::vertex_definition(),
+        )?;
+
+        let filter = match scaling {
+            ScalingMode::Integer => Filter::Nearest,
+            _ => Filter::Linear,
+        };
+
+        let sampler = Sampler::new(
+            device,
+            SamplerCreateInfo {
+                mag_filter: filter,
+                min_filter: filter,
+                address_mode: [SamplerAddressMode::ClampToEdge; 3],
+                ..Default::default()
+            },
+        )?;
+
+        Ok(Self {
+            render_pass,
+            shader,
+            sampler,
+            camera: Arc::new(camera::OrthographicCamera::default()),
+        })
+    }
+
+    // Clears `final_image` and draws `source` into `rect`, given in pixels of `extent`, when
+    // there is one.
+    fn draw(
+        &mut self,
+        before_future: Box<dyn GpuFuture>,
+        source: Arc<dyn ImageViewAbstract>,
+        final_image: FinalImageView,
+        extent: [u32; 2],
+        rect: Option<[u32; 4]>,
+    ) -> Result<Box<dyn GpuFuture>> {
+        let [width, height] = [extent[0] as f32, extent[1] as f32];
+
+        let quad = Arc::new(postprocess::EffectQuad {
+            source,
+            sampler: self.sampler.clone(),
+            params: None,
+        });
+
+        // Maps the unit quad onto `rect` in clip space.
+        let info = rect.map(|[x, y, w, h]| {
+            let mut info = DrawInfo::default();
+            info.nonuniform_scale(2.0 * w as f32 / width, 2.0 * h as f32 / height, 1.0);
+            info.dest(x as f32 / width * 2.0 - 1.0, y as f32 / height * 2.0 - 1.0, 0.0);
+            info
+        });
+
+        let mut frame = self.render_pass.frame(
+            Color::black().into(),
+            before_future,
+            final_image,
+            self.camera.clone(),
+        )?;
+
+        let mut after_future = None;
+        while let Some(pass) = frame.next_pass()? {
+            after_future = match pass {
+                render_pass::frame::PassState::DrawPass(mut pass) => {
+                    if let Some(info) = info {
+                        pass.draw_with(quad.clone(), self.shader, info)?;
+                    }
+                    None
+                }
+                render_pass::frame::PassState::Finished(af) => Some(af),
+            }
+        }
+
+        after_future.ok_or_else(|| anyhow!("The present pass did not finish"))
+    }
+}
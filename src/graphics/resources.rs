@@ -0,0 +1,332 @@
+use crate::graphics::profiler::{FrameQueries, GpuProfiler, PassTiming};
+use crate::graphics::{InstanceData, Vertex, QUAD_VERTICES};
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use vulkano::buffer::cpu_pool::{CpuBufferPoolChunk, CpuBufferPoolSubbuffer};
+use vulkano::buffer::{BufferUsage, CpuBufferPool, ImmutableBuffer};
+use vulkano::descriptor_set::layout::DescriptorSetLayout;
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::Queue;
+use vulkano::memory::pool::StdMemoryPool;
+use vulkano::image::ImageViewAbstract;
+use vulkano::sampler::Sampler;
+use vulkano::sync::GpuFuture;
+
+use anyhow::Result;
+
+/// How many frames can be recorded before the resources of the oldest one are reused, unless
+/// `InterfaceBuilder::frames_in_flight` sets another count.
+pub const FRAMES_IN_FLIGHT: usize = 2;
+
+type Mat4 = [[f32; 4]; 4];
+
+// The image view, sampler and layout a texture descriptor set was written with. The set keeps
+// all three alive, so their addresses cannot be reused while the entry is cached.
+type TextureKey = (usize, usize, usize);
+
+// The layout a uniform set was written with and the bits of its matrix.
+type UniformKey = (usize, [u32; 16]);
+
+// A cached texture set and the frame it was last used in.
+struct TextureSet {
+    set: Arc<PersistentDescriptorSet>,
+    last_used: u64,
+}
+
+/// Allocates the short lived data draws need from pools instead of creating new buffers and
+/// descriptor sets for every draw.
+///
+/// Each frame in flight gets its own set of buffer pools that is recycled when `begin_frame`
+/// comes back around to it, which the renderer only does once the GPU has finished the frame
+/// that last used it. Descriptor sets binding a texture are cached until a full cycle of frame
+/// slots passes without a draw using them, which releases the texture, and uniform sets are
+/// cached for the frame so draws with the same camera share one.
+pub struct FrameResources {
+    quad: Arc<ImmutableBuffer<[Vertex]>>,
+    slots: Vec<FrameSlot>,
+    current: AtomicUsize,
+    draws_saved: AtomicUsize,
+    texture_sets: Mutex<HashMap<TextureKey, TextureSet>>,
+    frame: AtomicU64,
+    // The matrix of the camera the last scene frame was started with since `begin_frame`.
+    camera_mvp: Mutex<Option<[[f32; 4]; 4]>>,
+    profiler: Option<GpuProfiler>,
+    stats: Mutex<RenderStats>,
+}
+
+/// What the draws recorded during a frame cost.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RenderStats {
+    /// Drawables and `PipelineData` drawn by render pass frames, a merged batch counts once.
+    pub draw_calls: u32,
+    /// Instances drawn, summed over every draw call.
+    pub instances: u32,
+    /// Vertices, or indices for indexed draws, counting every instance.
+    pub vertices: u64,
+    /// Graphics pipelines bound into the frame's command buffers.
+    pub pipeline_binds: u32,
+    /// Descriptor sets created, cached sets that were reused are not counted.
+    pub descriptor_sets: u32,
+    /// Bytes written into buffers for this frame's draws.
+    pub bytes_uploaded: u64,
+    /// Secondary command buffers executed by render pass frames.
+    pub command_buffers: u32,
+    /// Draw calls batching merged away.
+    pub draws_saved: u32,
+}
+
+struct FrameSlot {
+    vertices: CpuBufferPool<Vertex>,
+    instances: CpuBufferPool<InstanceData>,
+    uniforms: CpuBufferPool<Mat4>,
+    uniform_bytes: CpuBufferPool<u8>,
+    uniform_sets: Mutex<HashMap<UniformKey, Arc<PersistentDescriptorSet>>>,
+}
+
+impl FrameResources {
+    pub fn new(queue: Arc<Queue>, frames: usize) -> Result<Arc<Self>> {
+        let device = queue.device().clone();
+        let profiler = GpuProfiler::new(&queue, frames)?;
+
+        let (quad, upload) = ImmutableBuffer::from_iter(
+            QUAD_VERTICES.iter().cloned(),
+            BufferUsage::vertex_buffer(),
+            queue,
+        )?;
+        upload.then_signal_fence_and_flush()?.wait(None)?;
+
+        let slots = (0..frames)
+            .map(|_| FrameSlot {
+                vertices: CpuBufferPool::vertex_buffer(device.clone()),
+                instances: CpuBufferPool::vertex_buffer(device.clone()),
+                uniforms: CpuBufferPool::uniform_buffer(device.clone()),
+                uniform_bytes: CpuBufferPool::uniform_buffer(device.clone()),
+                uniform_sets: Mutex::new(HashMap::new()),
+            })
+            .collect();
+
+        Ok(Arc::new(Self {
+            quad,
+            slots,
+            current: AtomicUsize::new(0),
+            draws_saved: AtomicUsize::new(0),
+            texture_sets: Mutex::new(HashMap::new()),
+            frame: AtomicU64::new(0),
+            camera_mvp: Mutex::new(None),
+            profiler,
+            stats: Mutex::new(RenderStats::default()),
+        }))
+    }
+
+    /// The number of frame slots.
+    pub fn frames_in_flight(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// The slot recorded into since the last `begin_frame`.
+    pub fn current_slot(&self) -> usize {
+        self.current.load(Ordering::Relaxed)
+    }
+
+    /// The slot the next `begin_frame` moves on to.
+    pub fn next_slot(&self) -> usize {
+        (self.current_slot() + 1) % self.slots.len()
+    }
+
+    /// Moves on to the next frame slot, called by the renderer at the start of every frame once
+    /// the GPU is done with the slot.
+    pub fn begin_frame(&self) {
+        let next = self.next_slot();
+        self.current.store(next, Ordering::Relaxed);
+        self.draws_saved.store(0, Ordering::Relaxed);
+        *self.stats.lock().unwrap() = RenderStats::default();
+        *self.camera_mvp.lock().unwrap() = None;
+        self.slots[next].uniform_sets.lock().unwrap().clear();
+
+        let frame = self.frame.fetch_add(1, Ordering::Relaxed) + 1;
+        let lifetime = self.slots.len() as u64;
+        self.texture_sets
+            .lock()
+            .unwrap()
+            .retain(|_, cached| frame - cached.last_used <= lifetime);
+
+        if let Some(profiler) = &self.profiler {
+            profiler.begin_frame(next);
+        }
+    }
+
+    /// The matrix of the camera the last render pass frame drawing the scene was started with
+    /// this frame, the engine's own post-processing and overlay passes are not counted.
+    pub fn camera_mvp(&self) -> Option<[[f32; 4]; 4]> {
+        *self.camera_mvp.lock().unwrap()
+    }
+
+    pub(crate) fn set_camera_mvp(&self, mvp: [[f32; 4]; 4]) {
+        *self.camera_mvp.lock().unwrap() = Some(mvp);
+    }
+
+    /// GPU time of every render pass frame and pass from a recent frame, empty when the
+    /// device cannot write timestamps.
+    pub fn gpu_timings(&self) -> Vec<PassTiming> {
+        self.profiler.as_ref().map_or_else(Vec::new, |p| p.timings())
+    }
+
+    pub(crate) fn reserve_timestamps(&self, render_pass: &str, passes: u8) -> Option<FrameQueries> {
+        self.profiler.as_ref().and_then(|p| p.reserve(render_pass, passes))
+    }
+
+    /// The unit quad shared by every sprite draw.
+    pub fn quad(&self) -> Arc<ImmutableBuffer<[Vertex]>> {
+        self.quad.clone()
+    }
+
+    pub fn vertices<I>(&self, vertices: I) -> Result<Arc<CpuBufferPoolChunk<Vertex, Arc<StdMemoryPool>>>>
+    where
+        I: IntoIterator<Item = Vertex>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let vertices = vertices.into_iter();
+        self.record_upload(vertices.len() * std::mem::size_of::<Vertex>());
+
+        Ok(self.slot().vertices.chunk(vertices)?)
+    }
+
+    pub fn instances<I>(&self, instances: I) -> Result<Arc<CpuBufferPoolChunk<InstanceData, Arc<StdMemoryPool>>>>
+    where
+        I: IntoIterator<Item = InstanceData>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let instances = instances.into_iter();
+        self.record_upload(instances.len() * std::mem::size_of::<InstanceData>());
+
+        Ok(self.slot().instances.chunk(instances)?)
+    }
+
+    pub fn uniform(&self, mvp: Mat4) -> Result<Arc<CpuBufferPoolSubbuffer<Mat4, Arc<StdMemoryPool>>>> {
+        self.record_upload(std::mem::size_of::<Mat4>());
+
+        Ok(self.slot().uniforms.next(mvp)?)
+    }
+
+    /// A uniform buffer holding `bytes` for this frame, for uniforms whose type the caller
+    /// only has as bytes.
+    pub fn uniform_bytes(&self, bytes: &[u8]) -> Result<Arc<CpuBufferPoolChunk<u8, Arc<StdMemoryPool>>>> {
+        self.record_upload(bytes.len());
+
+        Ok(self.slot().uniform_bytes.chunk(bytes.iter().copied())?)
+    }
+
+    /// A set with `mvp` in a uniform buffer at binding 0, shared by every draw this frame that
+    /// uses the same layout and matrix.
+    pub fn uniform_set(&self, layout: Arc<DescriptorSetLayout>, mvp: Mat4) -> Result<Arc<PersistentDescriptorSet>> {
+        let mut bits = [0u32; 16];
+        for (bit, value) in bits.iter_mut().zip(mvp.iter().flatten()) {
+            *bit = value.to_bits();
+        }
+        let key = (Arc::as_ptr(&layout) as usize, bits);
+
+        let slot = self.slot();
+        let mut sets = slot.uniform_sets.lock().unwrap();
+        if let Some(set) = sets.get(&key) {
+            return Ok(set.clone());
+        }
+
+        let set = PersistentDescriptorSet::new(
+            layout,
+            [WriteDescriptorSet::buffer(0, self.uniform(mvp)?)],
+        )?;
+        sets.insert(key, set.clone());
+        self.record_descriptor_sets(1);
+
+        Ok(set)
+    }
+
+    /// A set with `image` and `sampler` at binding 0, created once and reused for every draw
+    /// of the same image with the same sampler and layout until it goes unused for a cycle of
+    /// frame slots.
+    pub fn texture_set(
+        &self,
+        layout: Arc<DescriptorSetLayout>,
+        image: Arc<dyn ImageViewAbstract>,
+        sampler: Arc<Sampler>,
+    ) -> Result<Arc<PersistentDescriptorSet>> {
+        let key = (
+            Arc::as_ptr(&image) as *const () as usize,
+            Arc::as_ptr(&sampler) as usize,
+            Arc::as_ptr(&layout) as usize,
+        );
+
+        let frame = self.frame.load(Ordering::Relaxed);
+        let mut sets = self.texture_sets.lock().unwrap();
+        if let Some(cached) = sets.get_mut(&key) {
+            cached.last_used = frame;
+            return Ok(cached.set.clone());
+        }
+
+        let set = PersistentDescriptorSet::new(
+            layout,
+            [WriteDescriptorSet::image_view_sampler(0, image, sampler)],
+        )?;
+        sets.insert(key, TextureSet { set: set.clone(), last_used: frame });
+        self.record_descriptor_sets(1);
+
+        Ok(set)
+    }
+
+    /// Drops every cached texture set, releasing the images they keep alive.
+    pub fn clear_texture_sets(&self) {
+        self.texture_sets.lock().unwrap().clear();
+    }
+
+    /// How many draw calls batching merged away since the frame began.
+    pub fn draws_saved(&self) -> usize {
+        self.draws_saved.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn record_draws_saved(&self, count: usize) {
+        self.draws_saved.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// The costs recorded since the frame began.
+    pub fn stats(&self) -> RenderStats {
+        RenderStats {
+            draws_saved: self.draws_saved() as u32,
+            ..*self.stats.lock().unwrap()
+        }
+    }
+
+    /// Counts the vertices and instances a draw recorded, the draw call itself is counted by
+    /// the frame executing it.
+    pub fn record_vertices(&self, vertices: u32, instances: u32) {
+        let mut stats = self.stats.lock().unwrap();
+        stats.instances += instances;
+        stats.vertices += vertices as u64 * instances as u64;
+    }
+
+    pub(crate) fn record_draw_call(&self) {
+        self.stats.lock().unwrap().draw_calls += 1;
+    }
+
+    pub(crate) fn record_pipeline_bind(&self) {
+        self.stats.lock().unwrap().pipeline_binds += 1;
+    }
+
+    pub fn record_descriptor_sets(&self, count: u32) {
+        self.stats.lock().unwrap().descriptor_sets += count;
+    }
+
+    pub fn record_upload(&self, bytes: usize) {
+        self.stats.lock().unwrap().bytes_uploaded += bytes as u64;
+    }
+
+    pub(crate) fn record_command_buffer(&self) {
+        self.stats.lock().unwrap().command_buffers += 1;
+    }
+
+    fn slot(&self) -> &FrameSlot {
+        &self.slots[self.current.load(Ordering::Relaxed)]
+    }
+}
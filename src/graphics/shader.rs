@@ -1,16 +1,31 @@
 use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::SystemTime;
 
+use crate::graphics::bindings::Bindings;
 use crate::graphics::{BlendMode, PipelineData};
+use vulkano::buffer::{BufferContents, BufferUsage, CpuAccessibleBuffer};
+use vulkano::command_buffer::CommandBufferUsage;
+use vulkano::device::Queue;
+use vulkano::format::Format;
+use vulkano::image::{view::ImageView, ImageCreateFlags, ImageDimensions, ImageUsage, StorageImage};
+use vulkano::pipeline::ComputePipeline;
+use vulkano::sync::GpuFuture;
 use vulkano::pipeline::graphics::color_blend::ColorComponents;
 use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
 use vulkano::pipeline::graphics::input_assembly::PrimitiveTopology;
+use vulkano::pipeline::graphics::vertex_input::{IncompatibleVertexDefinitionError, VertexInputState};
 use vulkano::pipeline::graphics::viewport::ViewportState;
+use vulkano::pipeline::graphics::GraphicsPipelineCreationError;
 use vulkano::pipeline::Pipeline;
 use vulkano::pipeline::PipelineBindPoint;
 use vulkano::pipeline::StateMode;
+use vulkano::shader::spirv::{ExecutionModel, Spirv};
+use vulkano::shader::{reflect, ShaderInterface, ShaderModule};
 use vulkano::{
-    command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer},
+    command_buffer::{AutoCommandBufferBuilder, SecondaryAutoCommandBuffer},
     descriptor_set::layout::DescriptorSetLayout,
     pipeline::{
         graphics::color_blend::{
@@ -25,9 +40,13 @@ use vulkano::{
     shader::EntryPoint,
 };
 
+use anyhow::{anyhow, Result};
+
 #[derive(Clone, Copy)]
 pub enum VertexTopology {
     PointList,
+    LineList,
+    LineStrip,
     TriangleFan,
     TriangleList,
     TriangleStrip,
@@ -39,61 +58,455 @@ pub enum ShaderType {
     TessellationControl,
     TessellationEval,
     Geometry,
+    Compute,
     Default,
 }
 
 pub type ShaderId = usize;
-
-pub struct Shader<'s> {
-    pub vertex: EntryPoint<'s>,
-    pub fragment: EntryPoint<'s>,
+pub type ComputeId = usize;
+
+/// A vertex and fragment shader pair along with the topology they draw with.
+///
+/// Shaders can be created from modules compiled with `vulkano_shaders::shader!`, or loaded from
+/// SPIR-V at runtime with `from_spirv` and `from_spirv_files`. Shaders loaded from files remember
+/// where they came from so a `RenderPass` watching its shaders can rebuild them when they change.
+pub struct Shader {
+    pub vertex: Arc<ShaderModule>,
+    pub fragment: Arc<ShaderModule>,
     pub topology: VertexTopology,
-    // pub vertex_definition: Vd,
+    vertex_entry: String,
+    fragment_entry: String,
+    source: Option<ShaderFiles>,
+}
+
+struct ShaderFiles {
+    vertex: PathBuf,
+    fragment: PathBuf,
+    kind: SourceKind,
+}
+
+#[derive(Clone, Copy)]
+enum SourceKind {
+    Spirv,
+    #[cfg(feature = "glsl")]
+    Glsl,
+}
+
+impl Shader {
+    /// Creates a shader from already loaded modules, both using the `main` entry point.
+    pub fn new(vertex: Arc<ShaderModule>, fragment: Arc<ShaderModule>, topology: VertexTopology) -> Self {
+        Self {
+            vertex,
+            fragment,
+            topology,
+            vertex_entry: "main".to_string(),
+            fragment_entry: "main".to_string(),
+            source: None,
+        }
+    }
+
+    /// Loads a shader from SPIR-V bytes, the entry points are found by reflecting the modules.
+    pub fn from_spirv(device: Arc<Device>, vertex: &[u8], fragment: &[u8], topology: VertexTopology) -> Result<Self> {
+        Self::from_words(device, &spirv_words(vertex)?, &spirv_words(fragment)?, topology)
+    }
+
+    fn from_words(device: Arc<Device>, vertex: &[u32], fragment: &[u32], topology: VertexTopology) -> Result<Self> {
+        let (vertex, vertex_entry) = load_spirv(device.clone(), vertex, ExecutionModel::Vertex)?;
+        let (fragment, fragment_entry) = load_spirv(device, fragment, ExecutionModel::Fragment)?;
+
+        Ok(Self {
+            vertex,
+            fragment,
+            topology,
+            vertex_entry,
+            fragment_entry,
+            source: None,
+        })
+    }
+
+    /// Loads a shader from SPIR-V files on disk.
+    pub fn from_spirv_files<P: AsRef<Path>>(device: Arc<Device>, vertex: P, fragment: P, topology: VertexTopology) -> Result<Self> {
+        let mut shader = Self::from_spirv(
+            device,
+            &fs::read(vertex.as_ref())?,
+            &fs::read(fragment.as_ref())?,
+            topology,
+        )?;
+
+        shader.source = Some(ShaderFiles {
+            vertex: vertex.as_ref().to_path_buf(),
+            fragment: fragment.as_ref().to_path_buf(),
+            kind: SourceKind::Spirv,
+        });
+
+        Ok(shader)
+    }
+
+    /// Compiles a shader from GLSL source with `graphics::glsl::compile`.
+    ///
+    /// Compile errors are returned as a `GameError::ShaderCompileError`.
+    #[cfg(feature = "glsl")]
+    pub fn from_glsl(device: Arc<Device>, vertex: &str, fragment: &str, topology: VertexTopology) -> Result<Self> {
+        use crate::graphics::glsl::{compile, ShaderStage};
+
+        Self::from_words(
+            device,
+            &compile(vertex, ShaderStage::Vertex, "<vertex>")?,
+            &compile(fragment, ShaderStage::Fragment, "<fragment>")?,
+            topology,
+        )
+    }
+
+    /// Compiles a shader from GLSL files on disk, errors point at the file they came from.
+    #[cfg(feature = "glsl")]
+    pub fn from_glsl_files<P: AsRef<Path>>(device: Arc<Device>, vertex: P, fragment: P, topology: VertexTopology) -> Result<Self> {
+        use crate::graphics::glsl::{compile, ShaderStage};
+
+        let (vertex, fragment) = (vertex.as_ref(), fragment.as_ref());
+
+        let mut shader = Self::from_words(
+            device,
+            &compile(&fs::read_to_string(vertex)?, ShaderStage::Vertex, &vertex.display().to_string())?,
+            &compile(&fs::read_to_string(fragment)?, ShaderStage::Fragment, &fragment.display().to_string())?,
+            topology,
+        )?;
+
+        shader.source = Some(ShaderFiles {
+            vertex: vertex.to_path_buf(),
+            fragment: fragment.to_path_buf(),
+            kind: SourceKind::Glsl,
+        });
+
+        Ok(shader)
+    }
+
+    /// Loads the shader again from the files it was created from.
+    pub fn reload(&self, device: Arc<Device>) -> Result<Self> {
+        let files = match &self.source {
+            Some(files) => files,
+            None => return Err(anyhow!("Shader was not loaded from files and cannot be reloaded")),
+        };
+
+        match files.kind {
+            SourceKind::Spirv => Self::from_spirv_files(device, &files.vertex, &files.fragment, self.topology),
+            #[cfg(feature = "glsl")]
+            SourceKind::Glsl => Self::from_glsl_files(device, &files.vertex, &files.fragment, self.topology),
+        }
+    }
+
+    /// The most recent modification time of the files the shader was loaded from.
+    pub fn modified(&self) -> Option<SystemTime> {
+        let files = self.source.as_ref()?;
+        let vertex = fs::metadata(&files.vertex).and_then(|m| m.modified()).ok()?;
+        let fragment = fs::metadata(&files.fragment).and_then(|m| m.modified()).ok()?;
+        Some(vertex.max(fragment))
+    }
+
+    pub fn vertex_entry(&self) -> EntryPoint<'_> {
+        self.vertex.entry_point(&self.vertex_entry).unwrap()
+    }
+
+    pub fn fragment_entry(&self) -> EntryPoint<'_> {
+        self.fragment.entry_point(&self.fragment_entry).unwrap()
+    }
+}
+
+/// Returns the entry points of a SPIR-V module along with their execution models.
+pub fn spirv_entry_points(bytes: &[u8]) -> Result<Vec<(String, ExecutionModel)>> {
+    entry_points(&spirv_words(bytes)?)
+}
+
+fn entry_points(words: &[u32]) -> Result<Vec<(String, ExecutionModel)>> {
+    let spirv = Spirv::new(words)?;
+
+    Ok(reflect::entry_points(&spirv)
+        .map(|(name, model, _)| (name, model))
+        .collect())
+}
+
+const SPIRV_MAGIC: u32 = 0x0723_0203;
+
+// Reads the words of a SPIR-V binary written with either endianness.
+fn spirv_words(bytes: &[u8]) -> Result<Vec<u32>> {
+    if bytes.len() % 4 != 0 {
+        return Err(anyhow!("SPIR-V length {} is not a multiple of 4", bytes.len()));
+    }
+
+    let mut words: Vec<u32> = bytes
+        .chunks_exact(4)
+        .map(|c| u32::from_ne_bytes([c[0], c[1], c[2], c[3]]))
+        .collect();
+
+    match words.first() {
+        Some(&SPIRV_MAGIC) => {}
+        Some(magic) if magic.swap_bytes() == SPIRV_MAGIC => {
+            for word in &mut words {
+                *word = word.swap_bytes();
+            }
+        }
+        _ => return Err(anyhow!("Not a SPIR-V binary, the magic number is missing")),
+    }
+
+    Ok(words)
+}
+
+fn load_spirv(device: Arc<Device>, words: &[u32], model: ExecutionModel) -> Result<(Arc<ShaderModule>, String)> {
+    let entry = entry_points(words)?
+        .into_iter()
+        .find(|(_, m)| *m == model)
+        .map(|(name, _)| name)
+        .ok_or_else(|| anyhow!("SPIR-V module has no {:?} entry point", model))?;
+
+    // The module has been parsed and reflected above, vulkano does not validate it any further.
+    let module = unsafe { ShaderModule::from_words(device, words)? };
+
+    Ok((module, entry))
+}
+
+/// A compute shader and the pipeline built from it.
+///
+/// Dispatches are recorded into their own command buffer and chained onto a future, so the
+/// returned future can be passed to `RenderPass::frame` to run the compute work before the
+/// scene is drawn. Storage buffers written by the shader can then be drawn from with
+/// `PipelineData::vertex_buffer_from` and storage images sampled like any other texture.
+///
+/// # Examples
+/// ```ignore
+/// let cells = shader::storage_buffer(renderer.device.clone(), vec![Cell::default(); W * H])?;
+/// let sand = renderer.register_compute(sand_cs::load(renderer.device.clone())?)?;
+///
+/// // In draw:
+/// let bindings = Bindings::new(renderer.device.clone())
+///     .storage_buffer(0, 0, cells.clone())
+///     .push_constants(Grid { width: W as u32, height: H as u32 });
+/// let future = renderer.dispatch(sand, future, [W as u32 / 64, H as u32, 1], bindings)?;
+/// let mut frame = renderer.render_passes[0].frame(clear, future, renderer.final_image(), camera)?;
+/// ```
+pub struct ComputeProgram {
+    pub module: Arc<ShaderModule>,
+    pipeline: Arc<ComputePipeline>,
+}
+
+impl ComputeProgram {
+    /// Builds the pipeline from a module with a compute entry point named `main`.
+    pub fn new(device: Arc<Device>, module: Arc<ShaderModule>) -> Result<Self> {
+        let entry = module
+            .entry_point("main")
+            .ok_or_else(|| anyhow!("Compute shader has no entry point named main"))?;
+        let pipeline = ComputePipeline::new(device, entry, &(), None, |_| {})?;
+
+        Ok(Self { module, pipeline })
+    }
+
+    pub fn from_spirv(device: Arc<Device>, bytes: &[u8]) -> Result<Self> {
+        let (module, entry) = load_spirv(device.clone(), &spirv_words(bytes)?, ExecutionModel::GLCompute)?;
+        let pipeline = ComputePipeline::new(
+            device,
+            module.entry_point(&entry).unwrap(),
+            &(),
+            None,
+            |_| {},
+        )?;
+
+        Ok(Self { module, pipeline })
+    }
+
+    pub fn pipeline(&self) -> Arc<ComputePipeline> {
+        self.pipeline.clone()
+    }
+
+    /// Records a dispatch of `group_counts` work groups after `before_future`.
+    pub fn dispatch(
+        &self,
+        queue: Arc<Queue>,
+        before_future: Box<dyn GpuFuture>,
+        group_counts: [u32; 3],
+        bindings: Bindings,
+    ) -> Result<Box<dyn GpuFuture>> {
+        let mut builder = AutoCommandBufferBuilder::primary(
+            queue.device().clone(),
+            queue.family(),
+            CommandBufferUsage::OneTimeSubmit,
+        )?;
+
+        builder.bind_pipeline_compute(self.pipeline.clone());
+        bindings.record(&mut builder, PipelineBindPoint::Compute, self.pipeline.layout().clone())?;
+        builder.dispatch(group_counts)?;
+
+        Ok(before_future.then_execute(queue, builder.build()?)?.boxed())
+    }
+}
+
+/// A buffer compute shaders can write that can also be bound as a vertex, index or uniform buffer.
+pub fn storage_buffer<T>(device: Arc<Device>, data: Vec<T>) -> Result<Arc<CpuAccessibleBuffer<[T]>>>
+where
+    [T]: BufferContents,
+{
+    let usage = BufferUsage {
+        storage_buffer: true,
+        vertex_buffer: true,
+        index_buffer: true,
+        uniform_buffer: true,
+        transfer_source: true,
+        transfer_destination: true,
+        ..BufferUsage::none()
+    };
+
+    Ok(CpuAccessibleBuffer::from_iter(device, usage, false, data)?)
+}
+
+/// An image compute shaders can write that can also be sampled as a texture.
+pub fn storage_image(queue: Arc<Queue>, dimensions: [u32; 2], format: Format) -> Result<Arc<ImageView<StorageImage>>> {
+    let usage = ImageUsage {
+        storage: true,
+        sampled: true,
+        transfer_source: true,
+        transfer_destination: true,
+        ..ImageUsage::none()
+    };
+
+    let image = StorageImage::with_usage(
+        queue.device().clone(),
+        ImageDimensions::Dim2d {
+            width: dimensions[0],
+            height: dimensions[1],
+            array_layers: 1,
+        },
+        format,
+        usage,
+        ImageCreateFlags::none(),
+        [queue.family()],
+    )?;
+
+    Ok(ImageView::new_default(image)?)
+}
+
+// Lets a vertex definition be kept around by a `ShaderProgram` to rebuild its pipelines.
+#[derive(Clone)]
+struct SharedVertexDefinition(Arc<dyn VertexDefinition + Sync + Send>);
+
+unsafe impl VertexDefinition for SharedVertexDefinition {
+    fn definition(&self, interface: &ShaderInterface) -> Result<VertexInputState, IncompatibleVertexDefinitionError> {
+        self.0.definition(interface)
+    }
 }
 
 pub struct ShaderProgram {
     pipelines: PipelineObjectSet,
     current_mode: BlendMode,
+    source: Option<ProgramSource>,
+}
+
+// Everything needed to build the pipelines of a program again.
+struct ProgramSource {
+    device: Arc<Device>,
+    render_pass: Arc<RenderPass>,
+    vertex_definition: SharedVertexDefinition,
+    shader: Arc<Shader>,
+    modified: Option<SystemTime>,
+    blend_constants: [f32; 4],
+}
+
+impl ProgramSource {
+    fn build(&self, shader: &Shader, blend: BlendMode) -> Result<Arc<GraphicsPipeline>, GraphicsPipelineCreationError> {
+        new_pipeline(
+            self.device.clone(),
+            self.render_pass.clone(),
+            self.vertex_definition.clone(),
+            shader.topology,
+            shader.vertex_entry(),
+            shader.fragment_entry(),
+            blend.color_blend_state(self.blend_constants),
+        )
+    }
 }
 
 pub trait ShaderHandle {
+    /// Records a draw of `pipe_data` after checking its bindings against the pipeline layout.
     fn draw(
         &self,
-        command_buffer: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        command_buffer: &mut AutoCommandBufferBuilder<SecondaryAutoCommandBuffer>,
         pipe_data: Box<PipelineData>,
-    );
-    // fn set_blend_mode(&mut self, mode: BlendMode);
+    ) -> Result<()>;
+    /// Makes `mode` the current blend mode, building its pipeline the first time it is used.
+    fn set_blend_mode(&mut self, mode: BlendMode) -> Result<()>;
+    /// Sets the constants read by `BlendMode::Constant`, rebuilding its pipeline if it was built.
+    /// Pipelines of the other blend modes are kept.
+    fn set_blend_constants(&mut self, constants: [f32; 4]) -> Result<()>;
     fn blend_mode(&self) -> BlendMode;
     fn layout(&self) -> &[Arc<DescriptorSetLayout>];
     fn pipeline(&self) -> Arc<GraphicsPipeline>;
+    /// Rebuilds the pipelines if the shader sources changed, returns whether anything was rebuilt.
+    fn reload(&mut self) -> Result<bool> {
+        Ok(false)
+    }
 }
 
 impl ShaderHandle for ShaderProgram {
     fn draw(
         &self,
-        command_buffer: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        command_buffer: &mut AutoCommandBufferBuilder<SecondaryAutoCommandBuffer>,
         pipe_data: Box<PipelineData>,
-    ) {
-        command_buffer.bind_pipeline_graphics(self.pipeline().clone());
+    ) -> Result<()> {
+        let pipeline = self.pipeline();
+        let (buffers, bindings, indices, v_count, i_count) = pipe_data.flush();
 
-        let layout = self.layout()[1].clone();
+        command_buffer.bind_pipeline_graphics(pipeline.clone());
+        bindings.record(command_buffer, PipelineBindPoint::Graphics, pipeline.layout().clone())?;
 
-        let (buffers, descriptors, v_count, i_count) = pipe_data.flush();
+        command_buffer.bind_vertex_buffers(0, buffers);
 
-        let set =
-            vulkano::descriptor_set::PersistentDescriptorSet::new(layout.clone(), descriptors)
-                .unwrap();
+        match indices {
+            Some(indices) => {
+                indices.bind(command_buffer);
+                command_buffer.draw_indexed(indices.len(), i_count, 0, 0, 0)?;
+            }
+            None => {
+                command_buffer.draw(v_count, i_count, 0, 0)?;
+            }
+        }
 
-        command_buffer.bind_descriptor_sets(
-            PipelineBindPoint::Graphics,
-            self.pipeline().layout().clone(),
-            1,
-            set,
-        );
+        Ok(())
+    }
 
-        command_buffer.bind_vertex_buffers(0, buffers);
+    fn set_blend_mode(&mut self, mode: BlendMode) -> Result<()> {
+        if self.pipelines.get(&mode).is_none() {
+            let source = self
+                .source
+                .as_ref()
+                .ok_or_else(|| anyhow!("Shader program has no pipeline for the requested blend mode"))?;
 
-        command_buffer.draw(v_count, i_count, 0, 0).unwrap();
+            self.pipelines.insert(mode, source.build(&source.shader, mode)?);
+        }
+
+        self.current_mode = mode;
+
+        Ok(())
+    }
+
+    fn set_blend_constants(&mut self, constants: [f32; 4]) -> Result<()> {
+        let source = self
+            .source
+            .as_mut()
+            .ok_or_else(|| anyhow!("Shader program was built from a pipeline and cannot be rebuilt"))?;
+
+        if source.blend_constants == constants {
+            return Ok(());
+        }
+        let previous = std::mem::replace(&mut source.blend_constants, constants);
+
+        // Only the constant blend state reads the constants.
+        if self.pipelines.get(&BlendMode::Constant).is_some() {
+            match source.build(&source.shader, BlendMode::Constant) {
+                Ok(pipeline) => self.pipelines.insert(BlendMode::Constant, pipeline),
+                Err(e) => {
+                    source.blend_constants = previous;
+                    return Err(e.into());
+                }
+            }
+        }
+
+        Ok(())
     }
 
     fn blend_mode(&self) -> BlendMode {
@@ -111,6 +524,32 @@ impl ShaderHandle for ShaderProgram {
     fn pipeline(&self) -> Arc<GraphicsPipeline> {
         self.pipelines.get(&self.current_mode).unwrap().clone()
     }
+
+    fn reload(&mut self) -> Result<bool> {
+        let source = match &mut self.source {
+            Some(source) => source,
+            None => return Ok(false),
+        };
+
+        let modified = source.shader.modified();
+        if modified.is_none() || modified == source.modified {
+            return Ok(false);
+        }
+        // Remember the change even if it fails to build so a broken file is only reported once.
+        source.modified = modified;
+
+        let shader = source.shader.reload(source.device.clone())?;
+
+        let mut pipelines = PipelineObjectSet::new(16);
+        for mode in self.pipelines.modes() {
+            pipelines.insert(mode, source.build(&shader, mode)?);
+        }
+
+        source.shader = Arc::new(shader);
+        self.pipelines = pipelines;
+
+        Ok(true)
+    }
 }
 
 impl ShaderProgram {
@@ -118,32 +557,31 @@ impl ShaderProgram {
         device: Arc<Device>,
         render_pass: Arc<RenderPass>,
         vertex_type: Vd,
-        vertex_order: VertexTopology,
-        vertex_shader: EntryPoint,
-        fragment_shader: EntryPoint,
+        shader: Arc<Shader>,
         blend: BlendMode,
-    ) -> Self
-    // ) -> Result<Self, GraphicsPipelineCreationError>
+    ) -> Result<Self, GraphicsPipelineCreationError>
     where
         Vd: VertexDefinition + 'static + Sync + Send,
     {
-        let po = new_pipeline(
+        let source = ProgramSource {
             device,
             render_pass,
-            vertex_type,
-            vertex_order,
-            vertex_shader,
-            fragment_shader,
-            blend,
-        );
+            vertex_definition: SharedVertexDefinition(Arc::new(vertex_type)),
+            modified: shader.modified(),
+            shader,
+            blend_constants: DEFAULT_BLEND_CONSTANTS,
+        };
+
+        let po = source.build(&source.shader, blend)?;
 
         let mut pos = PipelineObjectSet::new(16);
         pos.insert(blend, po);
 
-        Self {
+        Ok(Self {
             pipelines: pos,
             current_mode: blend,
-        }
+            source: Some(source),
+        })
     }
 
     pub fn from_pipeline(mode: BlendMode, pipeline: Arc<GraphicsPipeline>) -> Self {
@@ -152,6 +590,7 @@ impl ShaderProgram {
         Self {
             pipelines: pipeline_os,
             current_mode: mode,
+            source: None,
         }
     }
 }
@@ -176,6 +615,10 @@ impl PipelineObjectSet {
         self.pipelines.get(blend_mode)
     }
 
+    pub fn modes(&self) -> impl Iterator<Item = BlendMode> + '_ {
+        self.pipelines.keys().copied()
+    }
+
     // pub fn mode(&self, mode: &BlendMode) -> Result<&GraphicsPipeline, GraphicsError> {
     //     match self.pipelines.get(&mode) {
     //         Some(po) => Ok(po),
@@ -191,23 +634,26 @@ pub fn new_pipeline<Vd>(
     vertex_order: VertexTopology,
     vertex_shader: EntryPoint,
     fragment_shader: EntryPoint,
-    blend: BlendMode,
-// ) -> Result<Arc<GraphicsPipeline>, GraphicsPipelineCreationError>
-) -> Arc<GraphicsPipeline>
+    color_blend: ColorBlendState,
+) -> Result<Arc<GraphicsPipeline>, GraphicsPipelineCreationError>
 where
     Vd: VertexDefinition + 'static + Sync + Send,
 {
     let mut pipeline = GraphicsPipeline::start()
         .vertex_input_state::<Vd>(vertex_type)
         .vertex_shader(vertex_shader, ())
-        .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+        .viewport_state(ViewportState::viewport_dynamic_scissor_dynamic(1))
         .fragment_shader(fragment_shader, ())
-        .color_blend_state(blend.into())
+        .color_blend_state(color_blend)
         .render_pass(Subpass::from(render_pass.clone(), 0).unwrap());
 
     pipeline = match vertex_order {
         VertexTopology::PointList => pipeline
             .input_assembly_state(InputAssemblyState::new().topology(PrimitiveTopology::PointList)),
+        VertexTopology::LineList => pipeline
+            .input_assembly_state(InputAssemblyState::new().topology(PrimitiveTopology::LineList)),
+        VertexTopology::LineStrip => pipeline
+            .input_assembly_state(InputAssemblyState::new().topology(PrimitiveTopology::LineStrip)),
         VertexTopology::TriangleFan => pipeline.input_assembly_state(
             InputAssemblyState::new().topology(PrimitiveTopology::TriangleFan),
         ),
@@ -219,16 +665,26 @@ where
         ),
     };
 
-    pipeline.build(device.clone()).unwrap()
+    pipeline.build(device.clone())
 }
 
+/// The blend constants used by pipelines unless a shader program sets its own.
+pub const DEFAULT_BLEND_CONSTANTS: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
 impl From<BlendMode> for ColorBlendState {
     fn from(blend_mode: BlendMode) -> Self {
+        blend_mode.color_blend_state(DEFAULT_BLEND_CONSTANTS)
+    }
+}
+
+impl BlendMode {
+    /// Builds the color blend state for this mode, `blend_constants` are only read by
+    /// `BlendMode::Constant`.
+    pub fn color_blend_state(self, blend_constants: [f32; 4]) -> ColorBlendState {
         let mut logic_op: Option<StateMode<LogicOp>> = None;
         let mut attach: Option<AttachmentBlend> = None;
-        let blend_constants: [f32; 4] = [1.0, 1.0, 1.0, 1.0]; // TODO implement these.
 
-        match blend_mode {
+        match self {
             BlendMode::Add => {
                 attach = Some(AttachmentBlend {
                     color_op: BlendOp::Add,
@@ -255,6 +711,57 @@ impl From<BlendMode> for ColorBlendState {
             BlendMode::Invert => {
                 logic_op = Some(StateMode::Fixed(LogicOp::Invert));
             }
+            BlendMode::Multiply => {
+                attach = Some(AttachmentBlend {
+                    color_op: BlendOp::Add,
+                    color_source: BlendFactor::DstColor,
+                    color_destination: BlendFactor::OneMinusSrcAlpha,
+                    alpha_op: BlendOp::Add,
+                    alpha_source: BlendFactor::DstAlpha,
+                    alpha_destination: BlendFactor::OneMinusSrcAlpha,
+                });
+            }
+            BlendMode::Replace => {}
+            BlendMode::Lighten => {
+                attach = Some(AttachmentBlend {
+                    color_op: BlendOp::Max,
+                    color_source: BlendFactor::One,
+                    color_destination: BlendFactor::One,
+                    alpha_op: BlendOp::Max,
+                    alpha_source: BlendFactor::One,
+                    alpha_destination: BlendFactor::One,
+                });
+            }
+            BlendMode::Darken => {
+                attach = Some(AttachmentBlend {
+                    color_op: BlendOp::Min,
+                    color_source: BlendFactor::One,
+                    color_destination: BlendFactor::One,
+                    alpha_op: BlendOp::Min,
+                    alpha_source: BlendFactor::One,
+                    alpha_destination: BlendFactor::One,
+                });
+            }
+            BlendMode::Premultiplied => {
+                attach = Some(AttachmentBlend {
+                    color_op: BlendOp::Add,
+                    color_source: BlendFactor::One,
+                    color_destination: BlendFactor::OneMinusSrcAlpha,
+                    alpha_op: BlendOp::Add,
+                    alpha_source: BlendFactor::One,
+                    alpha_destination: BlendFactor::OneMinusSrcAlpha,
+                });
+            }
+            BlendMode::Constant => {
+                attach = Some(AttachmentBlend {
+                    color_op: BlendOp::Add,
+                    color_source: BlendFactor::ConstantColor,
+                    color_destination: BlendFactor::OneMinusConstantColor,
+                    alpha_op: BlendOp::Add,
+                    alpha_source: BlendFactor::ConstantAlpha,
+                    alpha_destination: BlendFactor::OneMinusConstantAlpha,
+                });
+            }
         };
 
         return ColorBlendState {
@@ -268,3 +775,25 @@ impl From<BlendMode> for ColorBlendState {
         };
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_spirv_of_either_endianness() {
+        let words = [SPIRV_MAGIC, 0x0001_0000];
+        let little: Vec<u8> = words.iter().flat_map(|w| w.to_le_bytes()).collect();
+        let big: Vec<u8> = words.iter().flat_map(|w| w.to_be_bytes()).collect();
+
+        assert_eq!(spirv_words(&little).unwrap(), words);
+        assert_eq!(spirv_words(&big).unwrap(), words);
+    }
+
+    #[test]
+    fn rejects_non_spirv() {
+        assert!(spirv_words(b"#version 450").is_err());
+        assert!(spirv_words(&[]).is_err());
+        assert!(spirv_words(&[3, 2, 35]).is_err());
+    }
+}
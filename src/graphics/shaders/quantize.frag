@@ -0,0 +1,17 @@
+#version 450
+
+layout(location=0) in vec2 v_uv;
+layout(location=1) in vec4 v_color;
+
+layout(binding=0,set=1) uniform sampler2D t_tex;
+layout(binding=1,set=1) uniform params {
+    float levels; // Number of levels kept per color channel.
+} quantize;
+
+layout(location=0) out vec4 f_color;
+
+void main() {
+    vec4 color = texture(t_tex, v_uv) * v_color;
+    float levels = max(quantize.levels - 1.0, 1.0);
+    f_color = vec4(floor(color.rgb * levels + 0.5) / levels, color.a);
+}
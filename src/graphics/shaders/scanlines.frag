@@ -0,0 +1,18 @@
+#version 450
+
+layout(location=0) in vec2 v_uv;
+layout(location=1) in vec4 v_color;
+
+layout(binding=0,set=1) uniform sampler2D t_tex;
+layout(binding=1,set=1) uniform params {
+    float intensity; // How dark the gaps between lines are.
+    float count; // Number of scanlines across the height of the image.
+} scanlines;
+
+layout(location=0) out vec4 f_color;
+
+void main() {
+    vec4 color = texture(t_tex, v_uv) * v_color;
+    float line = 0.5 + 0.5 * sin(v_uv.y * scanlines.count * 6.28318530718);
+    f_color = vec4(color.rgb * (1.0 - line * scanlines.intensity), color.a);
+}
@@ -0,0 +1,17 @@
+#version 450 core
+
+layout(location = 0) in vec3 pos; // The position of the vertex.
+
+layout(location = 4) in vec4 color; // The one color used for the whole instance.
+layout(location = 5) in mat4 transform;
+
+layout(binding=0,set=0) uniform mvp {
+    mat4 mvp;
+} camera;
+
+layout(location = 0) out vec4 v_color;
+
+void main() {
+    v_color = color;
+    gl_Position = camera.mvp * transform * vec4(pos, 1.0);
+}
@@ -4,7 +4,7 @@ layout(location = 0) in vec3 pos; // The position of the vertex.
 layout(location = 1) in vec2 uv; // Texture coordinates.
 layout(location = 2) in vec4 vert_color; // Color value.
 
-layout(location = 3) in vec4 src; // Chooses the texture to use in the texture array.
+layout(location = 3) in vec4 src; // Offset and size of the region of the texture to sample.
 layout(location = 4) in vec4 color;
 layout(location = 5) in mat4 transform;
 
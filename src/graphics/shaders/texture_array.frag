@@ -0,0 +1,13 @@
+#version 450
+
+layout(location=0) in vec2 v_uv;
+layout(location=1) in vec4 v_color;
+layout(location=2) flat in uint v_layer;
+
+layout(binding=0,set=1) uniform sampler2DArray t_tex;
+
+layout(location=0) out vec4 f_color;
+
+void main() {
+    f_color = texture(t_tex, vec3(v_uv, float(v_layer))) * v_color;
+}
@@ -0,0 +1,26 @@
+#version 450 core
+
+layout(location = 0) in vec3 pos; // The position of the vertex.
+layout(location = 1) in vec2 uv; // Texture coordinates.
+layout(location = 2) in vec4 vert_color; // Color value.
+
+layout(location = 3) in vec4 src; // Offset and size of the region of the texture to sample.
+layout(location = 4) in vec4 color;
+layout(location = 5) in mat4 transform;
+layout(location = 9) in uint layer; // Chooses the texture to use in the texture array.
+
+layout(binding=0,set=0) uniform mvp {
+    mat4 mvp;
+} camera;
+
+layout(location = 0) out vec2 v_uv;
+layout(location = 1) out vec4 v_color;
+layout(location = 2) flat out uint v_layer;
+
+void main() {
+    v_uv = vec2(uv * src.zw + src.xy);
+    v_color = vert_color * color;
+    v_layer = layer;
+    vec4 position = transform * vec4(pos, 1.0);
+    gl_Position = camera.mvp * position;
+}
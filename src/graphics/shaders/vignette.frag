@@ -0,0 +1,19 @@
+#version 450
+
+layout(location=0) in vec2 v_uv;
+layout(location=1) in vec4 v_color;
+
+layout(binding=0,set=1) uniform sampler2D t_tex;
+layout(binding=1,set=1) uniform params {
+    float intensity; // How dark the corners get, 0 disables the effect.
+    float radius; // Distance from the center where the darkening starts.
+} vignette;
+
+layout(location=0) out vec4 f_color;
+
+void main() {
+    vec4 color = texture(t_tex, v_uv) * v_color;
+    float dist = distance(v_uv, vec2(0.5));
+    float shade = smoothstep(vignette.radius, vignette.radius + 0.5, dist);
+    f_color = vec4(color.rgb * (1.0 - shade * vignette.intensity), color.a);
+}
@@ -0,0 +1,650 @@
+use crate::graphics::mesh::Mesh;
+use crate::graphics::{Color, Rect, Vertex};
+
+use std::f32::consts::{PI, TAU};
+use std::sync::Arc;
+use vulkano::device::Device;
+
+use anyhow::Result;
+
+/// How many segments a full circle is split into, partial arcs use a proportional amount.
+pub const CURVE_SEGMENTS: u32 = 48;
+
+/// Miters longer than this many half widths are drawn as bevels instead.
+const MITER_LIMIT: f32 = 4.0;
+
+type Point = [f32; 2];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineJoin {
+    Miter,
+    Bevel,
+    Round,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineCap {
+    /// The line ends exactly at its end points.
+    Butt,
+    /// The line extends half its width past its end points.
+    Square,
+    Round,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StrokeOptions {
+    /// The width of the line, in the same units as the points.
+    pub width: f32,
+    pub join: LineJoin,
+    pub cap: LineCap,
+}
+
+impl StrokeOptions {
+    pub fn new(width: f32) -> Self {
+        Self {
+            width,
+            join: LineJoin::Miter,
+            cap: LineCap::Butt,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DrawMode {
+    Fill,
+    Stroke(StrokeOptions),
+}
+
+impl DrawMode {
+    pub fn stroke(width: f32) -> Self {
+        DrawMode::Stroke(StrokeOptions::new(width))
+    }
+}
+
+/// Indexed triangles produced by the shape functions, drawn as a triangle list.
+///
+/// Shapes can be combined with `append` and uploaded with `into_mesh`, then drawn with the
+/// `library::VERTEX_COLOR` shader.
+#[derive(Clone, Debug, Default)]
+pub struct Geometry {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+}
+
+impl Geometry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    pub fn append(&mut self, other: &Geometry) {
+        let offset = self.vertices.len() as u32;
+        self.vertices.extend_from_slice(&other.vertices);
+        self.indices.extend(other.indices.iter().map(|i| i + offset));
+    }
+
+    pub fn into_mesh(self, device: Arc<Device>) -> Result<Mesh> {
+        Mesh::indexed_u32(device, self.vertices, self.indices)
+    }
+
+    fn vertex(&mut self, p: Point, color: [f32; 4]) -> u32 {
+        self.vertices.push(Vertex {
+            pos: [p[0], p[1], 0.0],
+            uv: [0.0, 0.0],
+            vert_color: color,
+        });
+
+        self.vertices.len() as u32 - 1
+    }
+
+    fn triangle(&mut self, a: u32, b: u32, c: u32) {
+        self.indices.extend_from_slice(&[a, b, c]);
+    }
+
+    // Triangles from `center` to every consecutive pair of `points`.
+    fn fan(&mut self, center: Point, points: &[Point], color: [f32; 4]) {
+        let center = self.vertex(center, color);
+        let first = self.vertices.len() as u32;
+
+        for p in points {
+            self.vertex(*p, color);
+        }
+        for i in 1..points.len() as u32 {
+            self.triangle(center, first + i - 1, first + i);
+        }
+    }
+
+    fn fill_convex(&mut self, points: &[Point], color: [f32; 4]) {
+        let first = self.vertices.len() as u32;
+
+        for p in points {
+            self.vertex(*p, color);
+        }
+        for i in 2..points.len() as u32 {
+            self.triangle(first, first + i - 1, first + i);
+        }
+    }
+}
+
+pub fn rectangle(mode: DrawMode, rect: Rect, color: Color) -> Geometry {
+    let points = [
+        [rect.x, rect.y],
+        [rect.x + rect.w, rect.y],
+        [rect.x + rect.w, rect.y + rect.h],
+        [rect.x, rect.y + rect.h],
+    ];
+
+    convex(mode, &points, color)
+}
+
+/// A rectangle with its corners rounded by `radius`, which is clamped to half the shorter side.
+pub fn rounded_rectangle(mode: DrawMode, rect: Rect, radius: f32, color: Color) -> Geometry {
+    let r = radius.min(rect.w.abs() / 2.0).min(rect.h.abs() / 2.0).max(0.0);
+    let (left, right) = (rect.x.min(rect.x + rect.w), rect.x.max(rect.x + rect.w));
+    let (top, bottom) = (rect.y.min(rect.y + rect.h), rect.y.max(rect.y + rect.h));
+
+    let corners = [
+        ([right - r, bottom - r], 0.0),
+        ([left + r, bottom - r], PI / 2.0),
+        ([left + r, top + r], PI),
+        ([right - r, top + r], PI * 1.5),
+    ];
+
+    let mut points = Vec::new();
+    for (center, start) in corners {
+        points.extend(arc_points(center, [r, r], start, start + PI / 2.0));
+    }
+    points.dedup();
+
+    convex(mode, &points, color)
+}
+
+pub fn circle(mode: DrawMode, center: Point, radius: f32, color: Color) -> Geometry {
+    ellipse(mode, center, [radius, radius], color)
+}
+
+pub fn ellipse(mode: DrawMode, center: Point, radii: [f32; 2], color: Color) -> Geometry {
+    let mut points = arc_points(center, radii, 0.0, TAU);
+    points.pop();
+
+    convex(mode, &points, color)
+}
+
+/// An arc from `start` to `end` radians, filled as a pie slice or stroked as an open line.
+pub fn arc(mode: DrawMode, center: Point, radius: f32, start: f32, end: f32, color: Color) -> Geometry {
+    let points = arc_points(center, [radius, radius], start, end);
+
+    match mode {
+        DrawMode::Fill => {
+            let mut geometry = Geometry::new();
+            geometry.fan(center, &points, color.into());
+            geometry
+        }
+        DrawMode::Stroke(options) => stroke(&points, false, options, color),
+    }
+}
+
+/// A closed polygon, filling works for any simple polygon, convex or not.
+pub fn polygon(mode: DrawMode, points: &[Point], color: Color) -> Geometry {
+    match mode {
+        DrawMode::Fill => fill_polygon(points, color),
+        DrawMode::Stroke(options) => stroke(points, true, options, color),
+    }
+}
+
+/// An open line through `points`.
+pub fn polyline(options: StrokeOptions, points: &[Point], color: Color) -> Geometry {
+    stroke(points, false, options, color)
+}
+
+fn convex(mode: DrawMode, points: &[Point], color: Color) -> Geometry {
+    match mode {
+        DrawMode::Fill => {
+            let mut geometry = Geometry::new();
+            geometry.fill_convex(points, color.into());
+            geometry
+        }
+        DrawMode::Stroke(options) => stroke(points, true, options, color),
+    }
+}
+
+// Points along an elliptical arc, including both ends.
+fn arc_points(center: Point, radii: [f32; 2], start: f32, end: f32) -> Vec<Point> {
+    let segments = ((end - start).abs() / TAU * CURVE_SEGMENTS as f32).ceil().max(1.0) as u32;
+
+    (0..=segments)
+        .map(|i| {
+            let angle = start + (end - start) * i as f32 / segments as f32;
+            [center[0] + radii[0] * angle.cos(), center[1] + radii[1] * angle.sin()]
+        })
+        .collect()
+}
+
+// Ear clipping triangulation.
+fn fill_polygon(points: &[Point], color: Color) -> Geometry {
+    let mut geometry = Geometry::new();
+    if points.len() < 3 {
+        return geometry;
+    }
+
+    let color = color.into();
+    let first = geometry.vertices.len() as u32;
+    for p in points {
+        geometry.vertex(*p, color);
+    }
+
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    if signed_area(points) < 0.0 {
+        remaining.reverse();
+    }
+
+    while remaining.len() > 3 {
+        let n = remaining.len();
+        let ear = (0..n).find(|&i| {
+            let (ia, ib, ic) = (remaining[(i + n - 1) % n], remaining[i], remaining[(i + 1) % n]);
+            let (a, b, c) = (points[ia], points[ib], points[ic]);
+
+            cross(sub(b, a), sub(c, b)) > 0.0
+                && !remaining
+                    .iter()
+                    .any(|&j| j != ia && j != ib && j != ic && in_triangle(points[j], a, b, c))
+        });
+
+        // Self intersecting polygons run out of ears, keep what was triangulated so far.
+        let i = match ear {
+            Some(i) => i,
+            None => return geometry,
+        };
+
+        geometry.triangle(
+            first + remaining[(i + n - 1) % n] as u32,
+            first + remaining[i] as u32,
+            first + remaining[(i + 1) % n] as u32,
+        );
+        remaining.remove(i);
+    }
+
+    geometry.triangle(
+        first + remaining[0] as u32,
+        first + remaining[1] as u32,
+        first + remaining[2] as u32,
+    );
+
+    geometry
+}
+
+fn stroke(points: &[Point], closed: bool, options: StrokeOptions, color: Color) -> Geometry {
+    let mut geometry = Geometry::new();
+    let color = color.into();
+    let hw = options.width / 2.0;
+
+    let mut points = points.to_vec();
+    points.dedup();
+    if closed && points.len() > 1 && points.first() == points.last() {
+        points.pop();
+    }
+
+    let n = points.len();
+    if n < 2 {
+        return geometry;
+    }
+
+    if !closed && options.cap == LineCap::Square {
+        points[0] = add(points[0], scale(direction(points[1], points[0]), hw));
+        points[n - 1] = add(points[n - 1], scale(direction(points[n - 2], points[n - 1]), hw));
+    }
+
+    let segments = if closed { n } else { n - 1 };
+    for i in 0..segments {
+        let (a, b) = (points[i], points[(i + 1) % n]);
+        let offset = scale(perp(direction(a, b)), hw);
+
+        let first = geometry.vertex(add(a, offset), color);
+        geometry.vertex(sub(a, offset), color);
+        geometry.vertex(sub(b, offset), color);
+        geometry.vertex(add(b, offset), color);
+        geometry.triangle(first, first + 1, first + 2);
+        geometry.triangle(first, first + 2, first + 3);
+    }
+
+    let joins = if closed { 0..n } else { 1..n - 1 };
+    for i in joins {
+        join(&mut geometry, points[(i + n - 1) % n], points[i], points[(i + 1) % n], hw, options.join, color);
+    }
+
+    if !closed && options.cap == LineCap::Round {
+        for (end, inner) in [(points[0], points[1]), (points[n - 1], points[n - 2])] {
+            let angle = angle_of(direction(inner, end));
+            let cap = arc_points(end, [hw, hw], angle + PI / 2.0, angle - PI / 2.0);
+            geometry.fan(end, &cap, color);
+        }
+    }
+
+    geometry
+}
+
+// Fills the gap on the outer side of the corner at `p` between the segments meeting there.
+fn join(geometry: &mut Geometry, prev: Point, p: Point, next: Point, hw: f32, join: LineJoin, color: [f32; 4]) {
+    let (d0, d1) = (direction(prev, p), direction(p, next));
+    let turn = cross(d0, d1);
+    if turn.abs() < 1e-6 {
+        return;
+    }
+
+    // Segments are offset along `perp`, which points left, so a left turn opens a gap on the right.
+    let side = if turn > 0.0 { -1.0 } else { 1.0 };
+    let (n0, n1) = (scale(perp(d0), side), scale(perp(d1), side));
+    let (o0, o1) = (add(p, scale(n0, hw)), add(p, scale(n1, hw)));
+
+    match join {
+        LineJoin::Bevel | LineJoin::Miter => {
+            let center = geometry.vertex(p, color);
+            let a = geometry.vertex(o0, color);
+            let b = geometry.vertex(o1, color);
+            geometry.triangle(center, a, b);
+
+            if join == LineJoin::Miter {
+                let bisector = normalize(add(n0, n1));
+                let length = hw / dot(bisector, n0);
+
+                if length <= MITER_LIMIT * hw {
+                    let tip = geometry.vertex(add(p, scale(bisector, length)), color);
+                    geometry.triangle(a, tip, b);
+                }
+            }
+        }
+        LineJoin::Round => {
+            let start = angle_of(n0);
+            let mut sweep = angle_of(n1) - start;
+            if sweep > PI {
+                sweep -= TAU;
+            } else if sweep < -PI {
+                sweep += TAU;
+            }
+
+            let points = arc_points(p, [hw, hw], start, start + sweep);
+            geometry.fan(p, &points, color);
+        }
+    }
+}
+
+fn signed_area(points: &[Point]) -> f32 {
+    let n = points.len();
+    (0..n).map(|i| cross(points[i], points[(i + 1) % n])).sum::<f32>() / 2.0
+}
+
+fn in_triangle(p: Point, a: Point, b: Point, c: Point) -> bool {
+    cross(sub(b, a), sub(p, a)) >= 0.0
+        && cross(sub(c, b), sub(p, b)) >= 0.0
+        && cross(sub(a, c), sub(p, c)) >= 0.0
+}
+
+fn add(a: Point, b: Point) -> Point {
+    [a[0] + b[0], a[1] + b[1]]
+}
+
+fn sub(a: Point, b: Point) -> Point {
+    [a[0] - b[0], a[1] - b[1]]
+}
+
+fn scale(a: Point, s: f32) -> Point {
+    [a[0] * s, a[1] * s]
+}
+
+fn dot(a: Point, b: Point) -> f32 {
+    a[0] * b[0] + a[1] * b[1]
+}
+
+fn cross(a: Point, b: Point) -> f32 {
+    a[0] * b[1] - a[1] * b[0]
+}
+
+fn perp(a: Point) -> Point {
+    [-a[1], a[0]]
+}
+
+fn angle_of(a: Point) -> f32 {
+    a[1].atan2(a[0])
+}
+
+fn normalize(a: Point) -> Point {
+    let length = dot(a, a).sqrt();
+    if length == 0.0 {
+        a
+    } else {
+        scale(a, 1.0 / length)
+    }
+}
+
+fn direction(from: Point, to: Point) -> Point {
+    normalize(sub(to, from))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-4;
+
+    fn triangles(geometry: &Geometry) -> Vec<[Point; 3]> {
+        geometry
+            .indices
+            .chunks(3)
+            .map(|t| {
+                let p = |i: u32| {
+                    let pos = geometry.vertices[i as usize].pos;
+                    [pos[0], pos[1]]
+                };
+                [p(t[0]), p(t[1]), p(t[2])]
+            })
+            .collect()
+    }
+
+    // The area covered by the triangles, counting overlaps twice.
+    fn area(geometry: &Geometry) -> f32 {
+        triangles(geometry)
+            .iter()
+            .map(|[a, b, c]| cross(sub(*b, *a), sub(*c, *a)).abs() / 2.0)
+            .sum()
+    }
+
+    fn bounds(geometry: &Geometry) -> [f32; 4] {
+        geometry.vertices.iter().fold(
+            [f32::MAX, f32::MAX, f32::MIN, f32::MIN],
+            |[x0, y0, x1, y1], v| [x0.min(v.pos[0]), y0.min(v.pos[1]), x1.max(v.pos[0]), y1.max(v.pos[1])],
+        )
+    }
+
+    fn has_vertex(geometry: &Geometry, p: Point) -> bool {
+        geometry
+            .vertices
+            .iter()
+            .any(|v| (v.pos[0] - p[0]).abs() < EPSILON && (v.pos[1] - p[1]).abs() < EPSILON)
+    }
+
+    fn distance_to_segment(p: Point, a: Point, b: Point) -> f32 {
+        let ab = sub(b, a);
+        let t = (dot(sub(p, a), ab) / dot(ab, ab)).clamp(0.0, 1.0);
+        let d = sub(p, add(a, scale(ab, t)));
+        dot(d, d).sqrt()
+    }
+
+    fn furthest_from_line(geometry: &Geometry, points: &[Point]) -> f32 {
+        geometry
+            .vertices
+            .iter()
+            .map(|v| {
+                points
+                    .windows(2)
+                    .map(|s| distance_to_segment([v.pos[0], v.pos[1]], s[0], s[1]))
+                    .fold(f32::MAX, f32::min)
+            })
+            .fold(0.0, f32::max)
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < EPSILON, "{} != {}", a, b);
+    }
+
+    fn stroke_options(width: f32, join: LineJoin, cap: LineCap) -> StrokeOptions {
+        StrokeOptions { width, join, cap }
+    }
+
+    const CORNER: [Point; 3] = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0]];
+
+    #[test]
+    fn fill_convex_polygon() {
+        let square = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
+        let geometry = polygon(DrawMode::Fill, &square, Color::white());
+
+        assert_eq!(geometry.indices.len(), 6);
+        assert_close(area(&geometry), 1.0);
+    }
+
+    #[test]
+    fn fill_concave_polygon() {
+        let l_shape = [[0.0, 0.0], [2.0, 0.0], [2.0, 1.0], [1.0, 1.0], [1.0, 2.0], [0.0, 2.0]];
+        let geometry = polygon(DrawMode::Fill, &l_shape, Color::white());
+
+        assert_eq!(geometry.indices.len(), 12);
+        assert_close(area(&geometry), 3.0);
+    }
+
+    #[test]
+    fn fill_winding_does_not_matter() {
+        let l_shape = [[0.0, 0.0], [2.0, 0.0], [2.0, 1.0], [1.0, 1.0], [1.0, 2.0], [0.0, 2.0]];
+        let mut reversed = l_shape;
+        reversed.reverse();
+
+        let geometry = polygon(DrawMode::Fill, &reversed, Color::white());
+
+        assert_close(area(&geometry), 3.0);
+        // Every triangle is emitted counter clockwise whatever the input winding.
+        for [a, b, c] in triangles(&geometry) {
+            assert!(cross(sub(b, a), sub(c, a)) > 0.0);
+        }
+    }
+
+    #[test]
+    fn degenerate_input_is_empty() {
+        let white = Color::white();
+        let options = StrokeOptions::new(0.1);
+
+        assert!(polygon(DrawMode::Fill, &[], white).is_empty());
+        assert!(polygon(DrawMode::Fill, &[[0.0, 0.0], [1.0, 0.0]], white).is_empty());
+        assert!(polyline(options, &[], white).is_empty());
+        assert!(polyline(options, &[[1.0, 1.0]], white).is_empty());
+        assert!(polyline(options, &[[1.0, 1.0], [1.0, 1.0], [1.0, 1.0]], white).is_empty());
+        assert!(polygon(DrawMode::Stroke(options), &[[1.0, 1.0], [1.0, 1.0]], white).is_empty());
+    }
+
+    #[test]
+    fn repeated_points_are_skipped() {
+        let options = StrokeOptions::new(0.1);
+        let line = polyline(options, &[[0.0, 0.0], [1.0, 0.0]], Color::white());
+        let repeated = polyline(options, &[[0.0, 0.0], [0.0, 0.0], [1.0, 0.0], [1.0, 0.0]], Color::white());
+
+        assert_eq!(line.indices, repeated.indices);
+        assert_close(area(&repeated), 0.1);
+    }
+
+    #[test]
+    fn butt_cap_ends_at_the_end_points() {
+        let options = stroke_options(1.0, LineJoin::Miter, LineCap::Butt);
+        let geometry = polyline(options, &[[0.0, 0.0], [2.0, 0.0]], Color::white());
+
+        let [x0, y0, x1, y1] = bounds(&geometry);
+        assert_close(x0, 0.0);
+        assert_close(x1, 2.0);
+        assert_close(y0, -0.5);
+        assert_close(y1, 0.5);
+        assert_close(area(&geometry), 2.0);
+    }
+
+    #[test]
+    fn square_cap_extends_half_the_width() {
+        let options = stroke_options(1.0, LineJoin::Miter, LineCap::Square);
+        let geometry = polyline(options, &[[0.0, 0.0], [2.0, 0.0]], Color::white());
+
+        let [x0, _, x1, _] = bounds(&geometry);
+        assert_close(x0, -0.5);
+        assert_close(x1, 2.5);
+        assert_close(area(&geometry), 3.0);
+    }
+
+    #[test]
+    fn round_cap_stays_within_half_the_width() {
+        let options = stroke_options(1.0, LineJoin::Miter, LineCap::Round);
+        let points = [[0.0, 0.0], [2.0, 0.0]];
+        let geometry = polyline(options, &points, Color::white());
+
+        let [x0, _, x1, _] = bounds(&geometry);
+        assert_close(x0, -0.5);
+        assert_close(x1, 2.5);
+        assert!(furthest_from_line(&geometry, &points) <= 0.5 + EPSILON);
+        // Slightly less than the butt line plus a full circle, as the circle is a polygon.
+        let expected = 2.0 + PI * 0.25;
+        assert!(area(&geometry) < expected && area(&geometry) > expected * 0.99);
+    }
+
+    #[test]
+    fn miter_join_reaches_the_corner() {
+        let options = stroke_options(0.2, LineJoin::Miter, LineCap::Butt);
+        let geometry = polyline(options, &CORNER, Color::white());
+
+        assert!(has_vertex(&geometry, [1.1, -0.1]));
+        assert_close(furthest_from_line(&geometry, &CORNER), 0.1 * 2.0f32.sqrt());
+    }
+
+    #[test]
+    fn sharp_miter_falls_back_to_bevel() {
+        let options = stroke_options(0.2, LineJoin::Miter, LineCap::Butt);
+        let points = [[0.0, 0.0], [1.0, 0.0], [0.0, 0.05]];
+        let geometry = polyline(options, &points, Color::white());
+
+        assert!(furthest_from_line(&geometry, &points) <= 0.1 + EPSILON);
+    }
+
+    #[test]
+    fn bevel_join_cuts_the_corner() {
+        let options = stroke_options(0.2, LineJoin::Bevel, LineCap::Butt);
+        let geometry = polyline(options, &CORNER, Color::white());
+
+        assert!(!has_vertex(&geometry, [1.1, -0.1]));
+        assert!(has_vertex(&geometry, [1.0, -0.1]));
+        assert!(has_vertex(&geometry, [1.1, 0.0]));
+        assert!(furthest_from_line(&geometry, &CORNER) <= 0.1 + EPSILON);
+    }
+
+    #[test]
+    fn round_join_stays_within_half_the_width() {
+        let round = polyline(stroke_options(0.2, LineJoin::Round, LineCap::Butt), &CORNER, Color::white());
+        let bevel = polyline(stroke_options(0.2, LineJoin::Bevel, LineCap::Butt), &CORNER, Color::white());
+
+        assert!(furthest_from_line(&round, &CORNER) <= 0.1 + EPSILON);
+        assert!(area(&round) > area(&bevel));
+    }
+
+    #[test]
+    fn closed_stroke_joins_every_corner() {
+        let square = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
+        let options = stroke_options(0.2, LineJoin::Miter, LineCap::Butt);
+        let geometry = polygon(DrawMode::Stroke(options), &square, Color::white());
+
+        for corner in [[-0.1, -0.1], [1.1, -0.1], [1.1, 1.1], [-0.1, 1.1]] {
+            assert!(has_vertex(&geometry, corner));
+        }
+    }
+
+    #[test]
+    fn indices_stay_in_bounds() {
+        let mut geometry = polygon(DrawMode::Fill, &[[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]], Color::white());
+        geometry.append(&polyline(stroke_options(0.1, LineJoin::Round, LineCap::Round), &CORNER, Color::white()));
+        geometry.append(&circle(DrawMode::stroke(0.1), [0.0, 0.0], 1.0, Color::white()));
+
+        assert!(geometry.indices.iter().all(|&i| (i as usize) < geometry.vertices.len()));
+    }
+}
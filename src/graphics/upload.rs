@@ -0,0 +1,140 @@
+use crate::graphics::image::{read_png, Image};
+
+use std::path;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use vulkano::device::Queue;
+use vulkano::sampler::Sampler;
+use vulkano::sync::GpuFuture;
+
+use anyhow::Result;
+
+enum LoadState {
+    Loading,
+    Ready(Image),
+    Failed(String),
+}
+
+/// An image being loaded by `Uploader::load_image`, cloning it shares the same load.
+#[derive(Clone)]
+pub struct ImageHandle {
+    state: Arc<Mutex<LoadState>>,
+}
+
+impl ImageHandle {
+    /// Whether the image can be drawn.
+    pub fn is_ready(&self) -> bool {
+        matches!(*self.state.lock().unwrap(), LoadState::Ready(_))
+    }
+
+    /// The image once it can be drawn.
+    pub fn get(&self) -> Option<Image> {
+        match &*self.state.lock().unwrap() {
+            LoadState::Ready(image) => Some(image.clone()),
+            _ => None,
+        }
+    }
+
+    /// Why the image could not be loaded, if it failed.
+    pub fn error(&self) -> Option<String> {
+        match &*self.state.lock().unwrap() {
+            LoadState::Failed(e) => Some(e.clone()),
+            _ => None,
+        }
+    }
+}
+
+// Pixels decoded off the main thread, waiting to be uploaded.
+struct Decoded {
+    state: Arc<Mutex<LoadState>>,
+    sampler: Arc<Sampler>,
+    pixels: Result<(u32, u32, Vec<u8>)>,
+}
+
+/// Copies textures to the GPU on the device's dedicated transfer queue, or the graphics queue
+/// when it has none, without waiting for the copies.
+///
+/// The futures of submitted uploads are joined into the next frame started with
+/// `Renderer::begin_frame`, so draws in that frame and later ones wait for them on the GPU.
+pub struct Uploader {
+    queue: Arc<Queue>,
+    pending: Vec<Box<dyn GpuFuture>>,
+    sender: Sender<Decoded>,
+    receiver: Receiver<Decoded>,
+}
+
+impl Uploader {
+    pub fn new(queue: Arc<Queue>) -> Self {
+        let (sender, receiver) = channel();
+
+        Self {
+            queue,
+            pending: Vec::new(),
+            sender,
+            receiver,
+        }
+    }
+
+    /// The queue uploads are submitted to.
+    pub fn queue(&self) -> &Arc<Queue> {
+        &self.queue
+    }
+
+    /// Starts uploading 8 bit RGBA pixels, the image can be drawn from the next frame on.
+    pub fn upload_image(
+        &mut self,
+        sampler: Arc<Sampler>,
+        width: u32,
+        height: u32,
+        data: Vec<u8>,
+    ) -> Result<Image> {
+        let (image, upload) = Image::from_rgba(self.queue.clone(), sampler, width, height, data)?;
+        self.pending.push(upload.then_signal_semaphore_and_flush()?.boxed());
+
+        Ok(image)
+    }
+
+    /// Decodes a PNG on another thread and uploads it at the start of a later frame, the
+    /// handle is ready from that frame on.
+    pub fn load_image<P: AsRef<path::Path>>(&self, sampler: Arc<Sampler>, path: P) -> ImageHandle {
+        let state = Arc::new(Mutex::new(LoadState::Loading));
+        let path = path.as_ref().to_path_buf();
+        let sender = self.sender.clone();
+        let decoded_state = state.clone();
+
+        thread::spawn(move || {
+            let pixels = read_png(&path)
+                .map_err(|e| e.context(format!("Failed to load {}", path.display())));
+
+            // The uploader was dropped, nobody is waiting for the image.
+            let _ = sender.send(Decoded {
+                state: decoded_state,
+                sampler,
+                pixels,
+            });
+        });
+
+        ImageHandle { state }
+    }
+
+    /// Uploads the images decoded since the last call and returns the future of every upload
+    /// submitted since then, called by the renderer at the start of every frame.
+    pub(crate) fn flush(&mut self) -> Option<Box<dyn GpuFuture>> {
+        while let Ok(decoded) = self.receiver.try_recv() {
+            let loaded = decoded.pixels.and_then(|(width, height, data)| {
+                self.upload_image(decoded.sampler, width, height, data)
+            });
+
+            *decoded.state.lock().unwrap() = match loaded {
+                Ok(image) => LoadState::Ready(image),
+                Err(e) => {
+                    println!("Background image load failed: {:?}", e);
+                    LoadState::Failed(format!("{:?}", e))
+                }
+            };
+        }
+
+        self.pending.drain(..).reduce(|a, b| a.join(b).boxed())
+    }
+}
@@ -1,5 +1,7 @@
 use crate::conf::*;
 use crate::error::*;
+use crate::graphics::adapter::AdapterInfo;
+use crate::graphics::camera::ScreenCamera;
 
 #[allow(unused)]
 pub struct InterfaceBuilder {
@@ -31,29 +33,137 @@ impl InterfaceBuilder {
         self.configuration.window_mode = mode;
         self
     }
+
+    /// Renders at a fixed logical resolution that is scaled to the window when presented.
+    ///
+    /// `Renderer::final_image` then returns the logical size target, and mouse positions are
+    /// reported in logical pixels. A width or height of 0 is raised to 1.
+    pub fn virtual_resolution(mut self, resolution: VirtualResolution) -> Self {
+        self.configuration.virtual_resolution = Some(VirtualResolution {
+            width: resolution.width.max(1),
+            height: resolution.height.max(1),
+            ..resolution
+        });
+        self
+    }
+
+    pub fn device(mut self, preference: DevicePreference) -> Self {
+        self.configuration.device = preference;
+        self
+    }
+
+    /// Runs with the Vulkan validation layer when it is installed, logging its messages of
+    /// `level` and above, and names Vulkan objects for graphics debuggers.
+    pub fn debug(mut self, level: DebugLevel) -> Self {
+        self.configuration.debug = level;
+        self
+    }
+
+    /// How many frames the CPU can record ahead of the GPU, at least one. More frames keep the
+    /// GPU busier at the cost of input latency and memory for each frame's buffers.
+    pub fn frames_in_flight(mut self, frames: usize) -> Self {
+        self.configuration.frames_in_flight = frames.max(1);
+        self
+    }
 }
 
 pub struct Interface {
     pub renderer: crate::graphics::renderer::Renderer,
+    /// Shapes added here during `update` are drawn over the scene at the end of the frame.
+    pub debug_draw: crate::graphics::debug_draw::DebugDraw,
     pub keyboard_context: crate::input::keyboard::KeyboardContext,
     pub mouse_context: crate::input::mouse::MouseContext,
     pub timer_state: crate::timer::TimerState,
+    ui_units: UiUnits,
+    pub(crate) pause_when_minimized: bool,
 }
 
 impl Interface {
     pub fn from_conf(instance_conf: Conf) -> GameResult<(Self, winit::event_loop::EventLoop<()>)> {
+        let ui_units = instance_conf.window_mode.ui_units;
+        let pause_when_minimized = instance_conf.window_mode.pause_when_minimized;
         let (renderer, event_loop) =
             crate::graphics::renderer::Renderer::new(instance_conf);
+        let debug_draw = crate::graphics::debug_draw::DebugDraw::new(&renderer).unwrap();
         let interface_ctx = Interface {
             renderer,
+            debug_draw,
             keyboard_context: crate::input::keyboard::KeyboardContext::new(),
             mouse_context: crate::input::mouse::MouseContext::new(),
             timer_state: crate::timer::TimerState::new(),
+            ui_units,
+            pause_when_minimized,
         };
 
         Ok((interface_ctx, event_loop))
     }
 
+    /// The device the renderer was created on.
+    pub fn adapter(&self) -> &AdapterInfo {
+        &self.renderer.adapter
+    }
+
+    /// The ratio of physical pixels to logical points of the display the window is on.
+    pub fn scale_factor(&self) -> f64 {
+        self.renderer.scale_factor()
+    }
+
+    /// The window size in physical pixels.
+    pub fn physical_size(&self) -> [u32; 2] {
+        self.renderer.window_size()
+    }
+
+    /// The window size in logical points.
+    pub fn logical_size(&self) -> [f64; 2] {
+        let [width, height] = self.physical_size();
+        let scale = self.scale_factor();
+
+        [width as f64 / scale, height as f64 / scale]
+    }
+
+    pub fn to_physical(&self, point: (f64, f64)) -> (f64, f64) {
+        let scale = self.scale_factor();
+        (point.0 * scale, point.1 * scale)
+    }
+
+    pub fn to_points(&self, pixel: (f64, f64)) -> (f64, f64) {
+        let scale = self.scale_factor();
+        (pixel.0 / scale, pixel.1 / scale)
+    }
+
+    /// How many pixels of the final image one UI unit covers, the scale factor when the UI is
+    /// drawn in points and 1 otherwise. Frames rendered at a virtual resolution are always
+    /// drawn in its pixels.
+    pub fn ui_scale(&self) -> f64 {
+        match self.ui_units {
+            UiUnits::Points if self.renderer.virtual_target.is_none() => self.scale_factor(),
+            _ => 1.0,
+        }
+    }
+
+    /// A camera covering the final image in the `UiUnits` from `Conf`.
+    pub fn ui_camera(&self) -> ScreenCamera {
+        let [width, height] = self.renderer.dimensions();
+        let scale = self.ui_scale() as f32;
+
+        ScreenCamera::new(width as f32 / scale, height as f32 / scale)
+    }
+
+    /// A camera covering the final image in its pixels.
+    pub fn world_camera(&self) -> ScreenCamera {
+        let [width, height] = self.renderer.dimensions();
+
+        ScreenCamera::new(width as f32, height as f32)
+    }
+
+    /// The mouse position in UI units.
+    pub fn mouse_ui_position(&self) -> (f64, f64) {
+        let (x, y) = self.mouse_context.last_position;
+        let scale = self.ui_scale();
+
+        (x / scale, y / scale)
+    }
+
     pub fn process_event(&mut self, event: &winit::event::Event<()>) {
         match event {
             // Window events.
@@ -61,9 +171,12 @@ impl Interface {
                 winit::event::WindowEvent::Resized(_) => {
                     self.renderer.recreate_swapchain = true;
                 }
+                winit::event::WindowEvent::ScaleFactorChanged { .. } => {
+                    self.renderer.recreate_swapchain = true;
+                }
                 winit::event::WindowEvent::CursorMoved { position, .. } => {
                     self.mouse_context
-                        .set_last_position((position.x, position.y));
+                        .set_last_position(self.renderer.to_logical((position.x, position.y)));
                 }
                 winit::event::WindowEvent::MouseInput { state, button, .. } => {
                     let button = match button {
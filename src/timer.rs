@@ -1,3 +1,5 @@
+use crate::graphics::profiler::PassTiming;
+
 use std::time;
 
 pub struct TimerState {
@@ -5,6 +7,7 @@ pub struct TimerState {
     last_instant: time::Instant,
     frame_times: Vec<time::Duration>,
     pub accumulator: time::Duration,
+    gpu_timings: Vec<PassTiming>,
 }
 
 impl Default for TimerState {
@@ -20,6 +23,7 @@ impl TimerState {
             last_instant: time::Instant::now(),
             frame_times: Vec::new(),
             accumulator: time::Duration::from_secs(0),
+            gpu_timings: Vec::new(),
         }
     }
 
@@ -32,6 +36,25 @@ impl TimerState {
         self.accumulator += frame_time;
     }
 
+    /// GPU time of every render pass frame and pass, from a frame or two ago since results are
+    /// read once the GPU is done with them.
+    pub fn gpu_timings(&self) -> &[PassTiming] {
+        &self.gpu_timings
+    }
+
+    /// Total GPU milliseconds of the render pass frames in `gpu_timings`.
+    pub fn gpu_frame_time(&self) -> f32 {
+        self.gpu_timings
+            .iter()
+            .filter(|t| t.pass.is_none())
+            .map(|t| t.milliseconds)
+            .sum()
+    }
+
+    pub fn set_gpu_timings(&mut self, timings: Vec<PassTiming>) {
+        self.gpu_timings = timings;
+    }
+
     pub fn alpha(&self) -> f32 {
         let target_dt = fps_as_duration(60);
         self.accumulator.as_secs_f32() / target_dt.as_secs_f32()